@@ -1,20 +1,210 @@
-use board;
-
-pub fn backtrack(board: &mut board::Board, addr: &board::Addr, depth: i32) -> bool {
-    for val in &board.legal_values(&addr) {
-        board.set(&addr, *val);
-        if depth < 80 {
-            let finished = backtrack(board, &board.next_addr(&addr), depth + 1);
-            if !finished {
-                board.unset(&addr);
-            } else {
-                return true;
-            }
+use std::fmt;
+
+/// Work done by a search.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Calls of the recursive search, one per node of the search tree.
+    pub calls: usize,
+    /// Values written to cells, including forced singles.
+    pub assignments: usize,
+    /// Values taken back out of cells.
+    pub undos: usize,
+    /// Forced singles filled without branching.
+    pub propagations: usize,
+}
+
+impl Stats {
+    /// Add the counts of `other` to these.
+    pub fn merge(&mut self, other: &Stats) {
+        self.calls += other.calls;
+        self.assignments += other.assignments;
+        self.undos += other.undos;
+        self.propagations += other.propagations;
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "calls: {}, assignments: {}, undos: {}, propagations: {}",
+            self.calls, self.assignments, self.undos, self.propagations
+        )
+    }
+}
+
+/// Order in which the search visits empty cells.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// Row-major order, as given by [`board::Board::next_addr`].
+    Naive,
+    /// Fill every cell with a single legal value, then branch on the empty
+    /// cell with the fewest legal values.
+    Mrv,
+}
+
+/// Solve `board` in place, returning whether a solution was found.
+pub fn solve(board: &mut board::Board, order: Order) -> bool {
+    solve_with_stats(board, order, &mut Stats::default())
+}
+
+/// Solve `board` in place like [`solve`], adding the work done to `stats`.
+pub fn solve_with_stats(board: &mut board::Board, order: Order, stats: &mut Stats) -> bool {
+    let mut soln = None;
+    search(board, order, stats, &mut |b| {
+        soln = Some(b.clone());
+        false
+    });
+    match soln {
+        Some(soln) => {
+            *board = soln;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Every solution of `board`.
+pub fn solve_all(board: &board::Board, order: Order) -> Vec<board::Board> {
+    let mut board = board.clone();
+    let mut solns = Vec::new();
+    search(&mut board, order, &mut Stats::default(), &mut |soln| {
+        solns.push(soln.clone());
+        true
+    });
+    solns
+}
+
+/// Number of solutions of `board`, counting no further than `limit`.
+pub fn count_solutions(board: &board::Board, order: Order, limit: usize) -> usize {
+    count_solutions_with_stats(board, order, limit, &mut Stats::default())
+}
+
+/// Count the solutions of `board` like [`count_solutions`], adding the work
+/// done to `stats`.
+pub fn count_solutions_with_stats(
+    board: &board::Board,
+    order: Order,
+    limit: usize,
+    stats: &mut Stats,
+) -> usize {
+    let mut board = board.clone();
+    let mut count = 0;
+    if limit > 0 {
+        search(&mut board, order, stats, &mut |_| {
+            count += 1;
+            count < limit
+        });
+    }
+    count
+}
+
+/// Visit every solution of `board` in the given order, passing each to `found`.
+///
+/// The search stops as soon as `found` returns `false`, and the return value
+/// says whether it ran to completion. `board` is left as it was given either way.
+pub fn search(
+    board: &mut board::Board,
+    order: Order,
+    stats: &mut Stats,
+    found: &mut dyn FnMut(&board::Board) -> bool,
+) -> bool {
+    match order {
+        Order::Naive => search_all(board, &board::Addr { row: 1, col: 1 }, stats, found),
+        Order::Mrv => search_mrv(board, stats, found),
+    }
+}
+
+/// Visit every solution of `board`, filling cells in row-major order from `addr`.
+fn search_all(
+    board: &mut board::Board,
+    addr: &board::Addr,
+    stats: &mut Stats,
+    found: &mut dyn FnMut(&board::Board) -> bool,
+) -> bool {
+    stats.calls += 1;
+    // a given stays put, so only count the values written to empty cells
+    let empty = board.can_set(addr);
+    for val in &board.legal_values(addr) {
+        board.set(addr, *val);
+        if empty {
+            stats.assignments += 1;
+        }
+        let more = if addr.row < board.size() || addr.col < board.size() {
+            search_all(board, &board.next_addr(addr), stats, found)
         } else {
-            return true;
+            found(board)
+        };
+        board.unset(addr);
+        if empty {
+            stats.undos += 1;
+        }
+        if !more {
+            return false;
         }
     }
-    return false;
+    true
+}
+
+/// Visit every solution of `board`, branching on the most constrained cell.
+fn search_mrv(
+    board: &mut board::Board,
+    stats: &mut Stats,
+    found: &mut dyn FnMut(&board::Board) -> bool,
+) -> bool {
+    stats.calls += 1;
+    let mut forced = Vec::new();
+    let more = loop {
+        match most_constrained(board) {
+            None => break found(board),
+            Some((_, 0)) => break true,
+            Some((addr, mask)) if mask.count_ones() == 1 => {
+                board.set(&addr, mask.trailing_zeros() as u8);
+                stats.assignments += 1;
+                stats.propagations += 1;
+                forced.push(addr);
+            }
+            Some((addr, mask)) => {
+                let mut more = true;
+                for val in (1..=board.size()).filter(|d| mask & (1 << d) != 0) {
+                    board.set(&addr, val);
+                    stats.assignments += 1;
+                    more = search_mrv(board, stats, found);
+                    board.unset(&addr);
+                    stats.undos += 1;
+                    if !more {
+                        break;
+                    }
+                }
+                break more;
+            }
+        }
+    };
+    for addr in forced {
+        board.unset(&addr);
+        stats.undos += 1;
+    }
+    more
+}
+
+/// Empty cell with the fewest legal values, and those values as a bitmask.
+///
+/// Returns `None` once every cell is filled.
+fn most_constrained(board: &board::Board) -> Option<(board::Addr, u32)> {
+    let mut best: Option<(board::Addr, u32)> = None;
+    for addr in board.addrs() {
+        if board.get(&addr) != 0 {
+            continue;
+        }
+        let mask = board.candidates(&addr);
+        if best.is_none_or(|(_, m)| mask.count_ones() < m.count_ones()) {
+            best = Some((addr, mask));
+            if mask.count_ones() <= 1 {
+                return best;
+            }
+        }
+    }
+    best
 }
 
 #[cfg(test)]
@@ -48,8 +238,116 @@ mod tests {
         249|156|783\n\
         756|389|142\n\
         ";
-        let mut board = board::Board::new(&BOARD_STRING);
-        backtrack(&mut board, &board::Addr { row: 1, col: 1 }, 0);
+        let mut board = board::Board::new(BOARD_STRING);
+        assert!(solve(&mut board, Order::Naive));
         assert_eq!(board.string(), exp);
     }
+
+    #[test]
+    fn test_solve_all() {
+        // the solution to the first of boards.txt with a 6/7 rectangle removed
+        let board = board::Board::new(
+            "534008912672195348198342567859001423426853791713924856961537284287419635345286179",
+        );
+        let solns: Vec<String> = solve_all(&board, Order::Naive)
+            .iter()
+            .map(|b| b.line())
+            .collect();
+        assert_eq!(
+            solns,
+            vec![
+                "534678912672195348198342567859761423426853791713924856961537284287419635345286179",
+                "534768912672195348198342567859671423426853791713924856961537284287419635345286179",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_solutions() {
+        let board = board::Board::new(
+            "534008912672195348198342567859001423426853791713924856961537284287419635345286179",
+        );
+        assert_eq!(count_solutions(&board, Order::Naive, 10), 2);
+        assert_eq!(count_solutions(&board, Order::Mrv, 10), 2);
+        assert_eq!(count_solutions(&board, Order::Naive, 1), 1);
+
+        // an empty board has billions of solutions
+        let board = board::Board::new(&"0".repeat(81));
+        assert_eq!(count_solutions(&board, Order::Naive, 2), 2);
+        assert_eq!(count_solutions(&board, Order::Mrv, 2), 2);
+    }
+
+    #[test]
+    fn test_solve_mrv() {
+        // the sixth of boards.txt, slow in row-major order
+        let mut board = board::Board::new(
+            "400000003008070450090006000000030008001000002600007130004050710800400000000000200",
+        );
+        assert!(solve(&mut board, Order::Mrv));
+        assert_eq!(
+            board.line(),
+            "417825963268973451593146827759231648381694572642587139924358716876412395135769284"
+        );
+
+        let mut board = board::Board::new(
+            "123456780000000009000000000000000000000000000000000000000000000000000000000000000",
+        );
+        assert!(!solve(&mut board, Order::Mrv));
+        assert_eq!(board.get(&board::Addr { row: 1, col: 9 }), 0);
+    }
+
+    #[test]
+    fn test_stats() {
+        let board = board::Board::new(
+            "534008912672195348198342567859001423426853791713924856961537284287419635345286179",
+        );
+        let mut stats = Stats::default();
+        assert!(solve_with_stats(
+            &mut board.to_owned(),
+            Order::Mrv,
+            &mut stats
+        ));
+        // every cell is a 6/7 pair: branch on one, the other three are forced
+        assert_eq!(
+            stats,
+            Stats {
+                calls: 2,
+                assignments: 4,
+                undos: 4,
+                propagations: 3,
+            }
+        );
+
+        let mut naive = Stats::default();
+        assert!(solve_with_stats(
+            &mut board.clone(),
+            Order::Naive,
+            &mut naive
+        ));
+        // givens are visited but never written, so only the four empty
+        // cells count
+        assert_eq!(naive.calls, 81);
+        assert_eq!((naive.assignments, naive.undos), (4, 4));
+        assert_eq!(naive.propagations, 0);
+    }
+
+    #[test]
+    fn test_sizes() {
+        let board = board::Board::new("1004 0000 0000 0320");
+        for order in [Order::Naive, Order::Mrv] {
+            let solns = solve_all(&board, order);
+            assert_eq!(solns.len(), 1);
+            assert_eq!(solns[0].line(), "1234341221434321");
+        }
+
+        let mut board = board::Board::new(&"0".repeat(36));
+        assert!(solve(&mut board, Order::Naive));
+        assert!(board.is_solved());
+
+        for len in [144, 256] {
+            let mut board = board::Board::new(&"0".repeat(len));
+            assert!(solve(&mut board, Order::Mrv));
+            assert!(board.is_solved());
+        }
+    }
 }
@@ -0,0 +1,254 @@
+//! Killer sudoku cages, and the text format they are read from.
+//!
+//! Each line of a cage file is one cage: its sum, then its cells. Blank
+//! lines and lines starting with `#` are comments.
+//!
+//! ```text
+//! # the top left corner
+//! 3 r1c1 r1c2
+//! 15 r1c3 r2c3 r3c3
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::Addr;
+
+/// Cells that hold different digits adding up to `sum`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cage {
+    pub sum: u32,
+    pub cells: Vec<Addr>,
+}
+
+/// Reason cages could not be read or put on a board, cages numbered from 1
+/// in the order given and lines from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CageError {
+    /// A line of a cage file is not a sum followed by cells.
+    Line { line: usize, text: String },
+    /// A cage has no cells.
+    Empty(usize),
+    /// A cage has a cell off the board.
+    OffBoard { cage: usize, addr: Addr },
+    /// A cell is in more than one cage.
+    Overlap(Addr),
+    /// No different digits in the cage's cells, with its givens, add up to its sum.
+    Sum { cage: usize, sum: u32 },
+}
+
+impl fmt::Display for CageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CageError::Line { line, text } => write!(f, "cannot read cage {text:?} on line {line}"),
+            CageError::Empty(cage) => write!(f, "cage {cage} has no cells"),
+            CageError::OffBoard { cage, addr } => {
+                write!(
+                    f,
+                    "cage {cage} has r{}c{} off the board",
+                    addr.row, addr.col
+                )
+            }
+            CageError::Overlap(addr) => write!(f, "r{}c{} is in two cages", addr.row, addr.col),
+            CageError::Sum { cage, sum } => write!(f, "cage {cage} cannot add up to {sum}"),
+        }
+    }
+}
+
+impl Error for CageError {}
+
+/// Read the cages of a cage file.
+pub fn parse(text: &str) -> Result<Vec<Cage>, CageError> {
+    let mut cages = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = || CageError::Line {
+            line: idx + 1,
+            text: line.to_string(),
+        };
+        let mut words = line.split_whitespace();
+        let sum = words
+            .next()
+            .and_then(|w| w.parse().ok())
+            .ok_or_else(error)?;
+        let cells = words
+            .map(|w| cell(w).ok_or_else(error))
+            .collect::<Result<Vec<Addr>, CageError>>()?;
+        cages.push(Cage { sum, cells });
+    }
+    Ok(cages)
+}
+
+/// Address written as `r3c5`.
+fn cell(word: &str) -> Option<Addr> {
+    let (row, col) = word.strip_prefix('r')?.split_once('c')?;
+    Some(Addr {
+        row: row.parse().ok()?,
+        col: col.parse().ok()?,
+    })
+}
+
+/// Most answers [`digits`] remembers before starting afresh.
+const MEMO_LIMIT: usize = 1 << 16;
+
+thread_local! {
+    /// Answers of [`digits`] by `(avail, count, sum)`, as the solvers ask
+    /// the same questions of a cage over and over.
+    static MEMO: RefCell<HashMap<(u32, u32, u32), Option<u32>>> = RefCell::new(HashMap::new());
+}
+
+/// Every digit in `avail`, a bitmask with bit `d` for digit `d`, that is
+/// part of some `count` different digits of `avail` adding up to `sum`, or
+/// `None` if there are no such digits.
+pub(crate) fn digits(avail: u32, count: u32, sum: u32) -> Option<u32> {
+    if count == 0 {
+        return (sum == 0).then_some(0);
+    }
+    if avail.count_ones() < count {
+        return None;
+    }
+    // the smallest and largest sums of `count` digits bound what is reachable
+    let mut low = avail;
+    let mut high = avail;
+    let (mut min, mut max) = (0, 0);
+    for _ in 0..count {
+        min += low.trailing_zeros();
+        low &= low - 1;
+        let top = 31 - high.leading_zeros();
+        max += top;
+        high &= !(1 << top);
+    }
+    if sum < min || sum > max {
+        return None;
+    }
+    let key = (avail, count, sum);
+    if let Some(found) = MEMO.with(|memo| memo.borrow().get(&key).copied()) {
+        return found;
+    }
+
+    let top = 31 - avail.leading_zeros();
+    let rest = avail & !(1 << top);
+    let with = match top <= sum {
+        true => digits(rest, count - 1, sum - top).map(|mask| mask | 1 << top),
+        false => None,
+    };
+    let found = match (with, digits(rest, count, sum)) {
+        (None, None) => None,
+        (with, without) => Some(with.unwrap_or(0) | without.unwrap_or(0)),
+    };
+    MEMO.with(|memo| {
+        let mut memo = memo.borrow_mut();
+        if memo.len() >= MEMO_LIMIT {
+            memo.clear();
+        }
+        memo.insert(key, found);
+    });
+    found
+}
+
+/// Every set of `count` different digits of `avail` adding up to `sum`, as
+/// bitmasks like `avail`, from the smallest digits up.
+pub fn combinations(avail: u32, count: u32, sum: u32) -> Vec<u32> {
+    let mut combos = Vec::new();
+    if let Some(digits) = digits(avail, count, sum) {
+        if count == 0 {
+            combos.push(0);
+            return combos;
+        }
+        // only digits in some combination are worth trying
+        let low = digits.trailing_zeros();
+        let rest = digits & !(1 << low);
+        combos.extend(
+            combinations(rest, count - 1, sum - low)
+                .into_iter()
+                .map(|combo| combo | 1 << low),
+        );
+        combos.extend(combinations(rest, count, sum));
+    }
+    combos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = "\
+        # the top left corner\n\
+        3 r1c1 r1c2\n\
+        \n\
+        15 r1c3 r2c3 r3c3\n\
+        ";
+        assert_eq!(
+            parse(text),
+            Ok(vec![
+                Cage {
+                    sum: 3,
+                    cells: vec![Addr { row: 1, col: 1 }, Addr { row: 1, col: 2 }]
+                },
+                Cage {
+                    sum: 15,
+                    cells: vec![
+                        Addr { row: 1, col: 3 },
+                        Addr { row: 2, col: 3 },
+                        Addr { row: 3, col: 3 }
+                    ]
+                },
+            ])
+        );
+        assert_eq!(
+            parse("3 r1c1 r1c2\nx r1c3\n"),
+            Err(CageError::Line {
+                line: 2,
+                text: "x r1c3".to_string()
+            })
+        );
+        assert!(parse("3 r1c1 1,2\n").is_err());
+    }
+
+    #[test]
+    fn test_digits() {
+        let all = 0b11_1111_1110;
+        // 3 in two cells is 1 + 2, 17 is 8 + 9
+        assert_eq!(digits(all, 2, 3), Some(0b110));
+        assert_eq!(digits(all, 2, 17), Some(0b11_0000_0000));
+        assert_eq!(digits(all, 2, 18), None);
+        assert_eq!(digits(all, 3, 6), Some(0b1110));
+        // 10 in two cells is anything but 5
+        assert_eq!(digits(all, 2, 10), Some(all & !(1 << 5)));
+        // without the 1, 10 in two cells can't use 9 either
+        assert_eq!(
+            digits(all & !0b10, 2, 10),
+            Some(all & !0b10 & !(1 << 5) & !(1 << 9))
+        );
+        assert_eq!(digits(all, 9, 45), Some(all));
+        assert_eq!(digits(all, 0, 0), Some(0));
+        assert_eq!(digits(all, 0, 1), None);
+
+        // 78 in twelve of sixteen cells is 1 to 12; 126 is 5 to 16
+        let all = 0b1_1111_1111_1111_1110;
+        assert_eq!(digits(all, 12, 78), Some(0b1_1111_1111_1110));
+        assert_eq!(digits(all, 12, 126), Some(all & !0b1_1110));
+        assert_eq!(digits(all, 12, 100), Some(all));
+        assert_eq!(digits(all, 12, 127), None);
+    }
+
+    #[test]
+    fn test_combinations() {
+        let all = 0b11_1111_1110;
+        assert_eq!(combinations(all, 2, 3), vec![0b110]);
+        // 10 in three cells: 1+2+7, 1+3+6, 1+4+5 and 2+3+5
+        assert_eq!(
+            combinations(all, 3, 10),
+            vec![0b1000_0110, 0b100_1010, 0b11_0010, 0b10_1100]
+        );
+        assert_eq!(combinations(all, 9, 45), vec![all]);
+        assert!(combinations(all, 2, 18).is_empty());
+    }
+}
@@ -0,0 +1,311 @@
+//! Rules tying the digits of a few cells together, beyond their houses.
+//!
+//! A constraint narrows the digits a cell may hold from the digits already
+//! placed in its other cells, so the backtracking solvers only need
+//! [`Board::candidates`] to keep to it, and it reports the placed digits
+//! that break it. Exact cover has no room for them, so the `dlx` solver
+//! refuses boards with constraints.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::{Addr, Board};
+
+/// A rule on the digits of some cells, see [`Board::with_constraints`].
+pub trait Constraint: fmt::Debug + Send + Sync {
+    /// Cells the rule ties together.
+    fn cells(&self) -> Vec<Addr>;
+
+    /// Digits `addr`, one of [`Constraint::cells`], may hold given the
+    /// digits in the others, as a bitmask with bit `d` for digit `d`.
+    fn candidates(&self, board: &Board, addr: &Addr) -> u32;
+
+    /// Cells holding a digit the rule does not allow beside the others.
+    fn violations(&self, board: &Board) -> Vec<Addr> {
+        self.cells()
+            .into_iter()
+            .filter(|addr| {
+                let val = board.get(addr);
+                val != 0 && self.candidates(board, addr) & 1 << val == 0
+            })
+            .collect()
+    }
+}
+
+/// Reason constraints could not be put on a board, constraints numbered
+/// from 1 in the order given.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConstraintError {
+    /// A constraint has a cell off the board.
+    OffBoard { constraint: usize, addr: Addr },
+    /// The digit at `addr` breaks a constraint.
+    Broken { constraint: usize, addr: Addr },
+}
+
+impl fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConstraintError::OffBoard { constraint, addr } => write!(
+                f,
+                "constraint {constraint} has r{}c{} off the board",
+                addr.row, addr.col
+            ),
+            ConstraintError::Broken { constraint, addr } => write!(
+                f,
+                "given at r{}c{} breaks constraint {constraint}",
+                addr.row, addr.col
+            ),
+        }
+    }
+}
+
+impl Error for ConstraintError {}
+
+/// Bitmask of the digits from `low` to `high`, clamped to `1..=size`.
+fn range(board: &Board, low: i32, high: i32) -> u32 {
+    let (low, high) = (low.max(1), high.min(board.size() as i32));
+    (low..=high).fold(0, |mask, d| mask | 1 << d)
+}
+
+/// A greater-than sign between two cells: `less` holds the smaller digit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Inequality {
+    pub less: Addr,
+    pub greater: Addr,
+}
+
+impl Constraint for Inequality {
+    fn cells(&self) -> Vec<Addr> {
+        vec![self.less, self.greater]
+    }
+
+    fn candidates(&self, board: &Board, addr: &Addr) -> u32 {
+        let n = board.size() as i32;
+        if *addr == self.less {
+            match board.get(&self.greater) as i32 {
+                0 => range(board, 1, n - 1),
+                val => range(board, 1, val - 1),
+            }
+        } else {
+            match board.get(&self.less) as i32 {
+                0 => range(board, 2, n),
+                val => range(board, val + 1, n),
+            }
+        }
+    }
+}
+
+/// Digits that rise strictly from the bulb, the first cell, to the tip.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Thermometer {
+    pub cells: Vec<Addr>,
+}
+
+impl Constraint for Thermometer {
+    fn cells(&self) -> Vec<Addr> {
+        self.cells.clone()
+    }
+
+    fn candidates(&self, board: &Board, addr: &Addr) -> u32 {
+        let len = self.cells.len() as i32;
+        let Some(pos) = self.cells.iter().position(|a| a == addr) else {
+            return 0;
+        };
+        let pos = pos as i32;
+        // room for a rising digit in every cell before and after this one
+        let (mut low, mut high) = (pos + 1, board.size() as i32 - (len - 1 - pos));
+        for (other, cell) in (0..).zip(&self.cells) {
+            match board.get(cell) as i32 {
+                0 => {}
+                _ if other == pos => {}
+                val if other < pos => low = low.max(val + pos - other),
+                val => high = high.min(val - (other - pos)),
+            }
+        }
+        range(board, low, high)
+    }
+}
+
+/// Digits along an arrow adding up to the digit in its circle.
+///
+/// Digits on the arrow may repeat unless they share a house.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Arrow {
+    pub circle: Addr,
+    pub cells: Vec<Addr>,
+}
+
+impl Constraint for Arrow {
+    fn cells(&self) -> Vec<Addr> {
+        let mut cells = vec![self.circle];
+        cells.extend(&self.cells);
+        cells
+    }
+
+    fn candidates(&self, board: &Board, addr: &Addr) -> u32 {
+        let n = board.size() as i32;
+        // the sum and number of empty cells along the arrow, but for `addr`
+        let (mut sum, mut empty) = (0, 0);
+        for cell in self.cells.iter().filter(|cell| *cell != addr) {
+            match board.get(cell) as i32 {
+                0 => empty += 1,
+                val => sum += val,
+            }
+        }
+        if *addr == self.circle {
+            return range(board, sum + empty, sum + empty * n);
+        }
+        match board.get(&self.circle) as i32 {
+            0 => range(board, 1, n - sum - empty),
+            circle => range(board, circle - sum - empty * n, circle - sum - empty),
+        }
+    }
+}
+
+/// Colour of a Kropki dot.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Dot {
+    /// The two digits are consecutive.
+    White,
+    /// One digit is twice the other.
+    Black,
+}
+
+/// A Kropki dot between two cells.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Kropki {
+    pub a: Addr,
+    pub b: Addr,
+    pub dot: Dot,
+}
+
+impl Constraint for Kropki {
+    fn cells(&self) -> Vec<Addr> {
+        vec![self.a, self.b]
+    }
+
+    fn candidates(&self, board: &Board, addr: &Addr) -> u32 {
+        let other = if *addr == self.a { &self.b } else { &self.a };
+        let partners = |val: u8| {
+            let val = val as i32;
+            match self.dot {
+                Dot::White => range(board, val - 1, val + 1) & !(1 << val),
+                Dot::Black if val % 2 == 0 => {
+                    range(board, val / 2, val / 2) | range(board, 2 * val, 2 * val)
+                }
+                Dot::Black => range(board, 2 * val, 2 * val),
+            }
+        };
+        match board.get(other) {
+            // any digit with a partner on the board
+            0 => (1..=board.size())
+                .filter(|d| partners(*d) != 0)
+                .fold(0, |mask, d| mask | 1 << d),
+            val => partners(val),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(row: u8, col: u8) -> Addr {
+        Addr { row, col }
+    }
+
+    fn digits(mask: u32) -> Vec<u8> {
+        (1..=9).filter(|d| mask & 1 << d != 0).collect()
+    }
+
+    #[test]
+    fn test_inequality() {
+        let mut board = Board::new(&"0".repeat(81));
+        let sign = Inequality {
+            less: addr(1, 1),
+            greater: addr(1, 2),
+        };
+        assert_eq!(
+            digits(sign.candidates(&board, &addr(1, 1))),
+            (1..=8).collect::<Vec<_>>()
+        );
+        board.set(&addr(1, 2), 4);
+        assert_eq!(digits(sign.candidates(&board, &addr(1, 1))), vec![1, 2, 3]);
+        board.set(&addr(1, 1), 5);
+        assert_eq!(sign.violations(&board), vec![addr(1, 1), addr(1, 2)]);
+    }
+
+    #[test]
+    fn test_thermometer() {
+        let mut board = Board::new(&"0".repeat(81));
+        let thermo = Thermometer {
+            cells: vec![addr(1, 1), addr(2, 1), addr(3, 1), addr(4, 1)],
+        };
+        assert_eq!(
+            digits(thermo.candidates(&board, &addr(2, 1))),
+            vec![2, 3, 4, 5, 6, 7]
+        );
+        board.set(&addr(4, 1), 5);
+        assert_eq!(digits(thermo.candidates(&board, &addr(1, 1))), vec![1, 2]);
+        assert_eq!(digits(thermo.candidates(&board, &addr(2, 1))), vec![2, 3]);
+        board.set(&addr(1, 1), 3);
+        assert_eq!(thermo.candidates(&board, &addr(2, 1)), 0);
+        assert_eq!(thermo.violations(&board), vec![addr(1, 1), addr(4, 1)]);
+    }
+
+    #[test]
+    fn test_arrow() {
+        let mut board = Board::new(&"0".repeat(81));
+        let arrow = Arrow {
+            circle: addr(1, 1),
+            cells: vec![addr(1, 2), addr(2, 3)],
+        };
+        assert_eq!(
+            digits(arrow.candidates(&board, &addr(1, 1))),
+            (2..=9).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            digits(arrow.candidates(&board, &addr(1, 2))),
+            (1..=8).collect::<Vec<_>>()
+        );
+        board.set(&addr(1, 1), 4);
+        assert_eq!(digits(arrow.candidates(&board, &addr(1, 2))), vec![1, 2, 3]);
+        board.set(&addr(2, 3), 3);
+        assert_eq!(digits(arrow.candidates(&board, &addr(1, 2))), vec![1]);
+        board.set(&addr(1, 2), 1);
+        assert!(arrow.violations(&board).is_empty());
+        board.set(&addr(1, 2), 2);
+        assert_eq!(arrow.violations(&board).len(), 3);
+    }
+
+    #[test]
+    fn test_kropki() {
+        let mut board = Board::new(&"0".repeat(81));
+        let white = Kropki {
+            a: addr(1, 1),
+            b: addr(1, 2),
+            dot: Dot::White,
+        };
+        let black = Kropki {
+            dot: Dot::Black,
+            ..white.clone()
+        };
+        assert_eq!(
+            digits(white.candidates(&board, &addr(1, 1))),
+            (1..=9).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            digits(black.candidates(&board, &addr(1, 1))),
+            vec![1, 2, 3, 4, 6, 8]
+        );
+        board.set(&addr(1, 2), 4);
+        assert_eq!(digits(white.candidates(&board, &addr(1, 1))), vec![3, 5]);
+        assert_eq!(digits(black.candidates(&board, &addr(1, 1))), vec![2, 8]);
+        board.set(&addr(1, 2), 9);
+        assert_eq!(digits(white.candidates(&board, &addr(1, 1))), vec![8]);
+        assert_eq!(black.candidates(&board, &addr(1, 1)), 0);
+        board.set(&addr(1, 1), 8);
+        assert!(white.violations(&board).is_empty());
+        assert_eq!(black.violations(&board).len(), 2);
+    }
+}
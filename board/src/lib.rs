@@ -1,8 +1,15 @@
 //! Sudoku game
 
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::error::Error;
 use std::fmt;
+use std::sync::{Arc, Mutex};
+
+pub mod cage;
+pub mod constraint;
+
+use cage::{Cage, CageError};
+use constraint::{Constraint, ConstraintError};
 
 /// Address of a Cell on a sudoku board.
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -21,13 +28,13 @@ pub struct Cell {
 
 impl PartialEq for Cell {
     fn eq(&self, other: &Self) -> bool {
-        return self.addr.eq(&other.addr);
+        self.addr.eq(&other.addr)
     }
 }
 
 impl Ord for Cell {
     fn cmp(&self, other: &Self) -> Ordering {
-        return self.addr.cmp(&other.addr);
+        self.addr.cmp(&other.addr)
     }
 }
 
@@ -39,18 +46,15 @@ impl PartialOrd for Cell {
 
 impl Cell {
     pub fn new(val: u8, row: u8, col: u8) -> Cell {
-        return Cell {
+        Cell {
             addr: Addr { row, col },
-            og: match val {
-                0 => false,
-                _ => true,
-            },
+            og: val != 0,
             val,
-        };
+        }
     }
 
     pub fn can_set(&self) -> bool {
-        return !self.og;
+        !self.og
     }
 
     pub fn set(&mut self, val: u8) {
@@ -66,163 +70,1014 @@ impl Cell {
     }
 
     pub fn is_set(&self) -> bool {
-        return self.val > 0;
+        self.val > 0
     }
 }
 
-/// 9x9 Sudoku board.
+/// Box height and width of the board sizes [`Board::parse`] recognises.
+const SHAPES: [(u8, u8); 6] = [(2, 2), (2, 3), (3, 3), (3, 4), (4, 4), (5, 5)];
+
+/// Number of cells on a board with boxes `box_rows` high and `box_cols` wide.
+fn cell_count(box_rows: u8, box_cols: u8) -> usize {
+    (box_rows as usize * box_cols as usize).pow(2)
+}
+
+/// Largest number of digits a board may have.
+pub const MAX_SIZE: u8 = 25;
+
+/// Sudoku board of any size from 4x4 to 25x25, its boxes `box_rows` high
+/// and `box_cols` wide, or a jigsaw board whose boxes are irregular regions.
+///
+/// Cells are stored in row-major order, sized for the board's own layout,
+/// which boards of the same shape share. Each house keeps a bitmask of the
+/// digits placed in it, bit `d` standing for digit `d`, which is updated on
+/// every set and unset so legal values never need a scan.
+#[derive(Clone)]
 pub struct Board {
-    cells: HashMap<Addr, Cell>,
-    nhbrs: HashMap<Addr, [Addr; 20]>,
+    shape: Arc<Shape>,
+    cells: Box<[Cell]>,
+    /// Digits used in each house, in the order of [`Board::houses`].
+    used: Box<[u32]>,
+    /// How many cells of each house hold each digit, so that unsetting one
+    /// of two clashing cells leaves the digit marked as used. Indexed by
+    /// house, then digit.
+    counts: Box<[u8]>,
+    /// Sum of the digits placed in each cage.
+    cage_sums: Box<[u32]>,
+    /// Number of digits placed in each cage.
+    cage_filled: Box<[usize]>,
+}
+
+/// Extra rule a board can play by, on top of its rows, columns and boxes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Variant {
+    /// Each main diagonal holds every digit once.
+    Diagonal,
+    /// So does each of the windows lying between the boxes, four on a 9x9
+    /// board, as in Windoku. Only boards with square boxes have them.
+    Hyper,
+    /// Cells a king's move apart may not hold the same digit.
+    AntiKing,
+    /// Cells a knight's move apart may not hold the same digit.
+    AntiKnight,
+}
+
+/// Cells that may not repeat a digit. A house of one cell per digit, such
+/// as a row, holds each digit exactly once; a smaller one, such as two
+/// cells a knight's move apart, at most once.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct House {
+    /// Short name, such as `r3`, `c5` and `b2` for a row, column and box,
+    /// `d1` and `d2` for the diagonals, `w1` for a window, `k1` for a cage
+    /// and `r1c1-r2c3` for a pair of cells.
+    pub name: String,
+    /// Cells of the house, in row-major order.
+    pub cells: Vec<Addr>,
+}
+
+/// Layout shared by every board of one size, region map, set of variants,
+/// cages and constraints: which houses and neighbours each cell has.
+///
+/// Those of plain boards are built once for each box size and kept, see
+/// [`Shape::plain`]; the others are dropped with the last board using them.
+struct Shape {
+    /// Height and width of the boxes, `None` for a jigsaw.
+    boxes: Option<(u8, u8)>,
+    /// Digits, and cells along each side.
+    size: u8,
+    /// Region of each cell, counting from 1.
+    regions: Vec<u8>,
+    variants: Vec<Variant>,
+    cages: Vec<Cage>,
+    /// House of each cage.
+    cage_houses: Vec<usize>,
+    /// Cage holding each cell, if any.
+    cell_cages: Vec<Option<usize>>,
+    constraints: Vec<Arc<dyn Constraint>>,
+    /// Constraints on each cell.
+    cell_constraints: Vec<Vec<usize>>,
+    /// Rows, then columns, then regions, then those of the variants and cages.
+    houses: Vec<House>,
+    /// Indices of the houses holding each cell, in order.
+    cell_houses: Vec<Vec<usize>>,
+    /// Neighbours of each cell, in row-major order.
+    neighbours: Vec<Vec<Addr>>,
+}
+
+impl Shape {
+    /// Shape of a plain board with boxes `box_rows` high and `box_cols`
+    /// wide, built the first time it is asked for.
+    fn plain(box_rows: u8, box_cols: u8) -> Arc<Shape> {
+        static PLAIN: Mutex<Vec<Arc<Shape>>> = Mutex::new(Vec::new());
+        let mut plain = PLAIN.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(shape) = plain.iter().find(|s| s.boxes == Some((box_rows, box_cols))) {
+            return Arc::clone(shape);
+        }
+        let shape = Arc::new(Shape::new(box_rows, box_cols));
+        plain.push(Arc::clone(&shape));
+        shape
+    }
+
+    fn new(box_rows: u8, box_cols: u8) -> Shape {
+        let size = box_rows * box_cols;
+        if box_rows == 0 || box_cols == 0 || size > MAX_SIZE {
+            panic!("Invalid {box_rows}x{box_cols} boxes");
+        }
+        let regions = (0..size)
+            .flat_map(|r| (0..size).map(move |c| c / box_cols + r / box_rows * box_rows + 1))
+            .collect();
+        Shape::with_regions(size, Some((box_rows, box_cols)), regions, &[], &[], &[])
+    }
+
+    /// Shape of a jigsaw from its layout, see [`Board::parse_jigsaw`].
+    fn jigsaw(layout: &str) -> Result<Shape, LayoutError> {
+        let labels: Vec<char> = layout.chars().filter(|ch| !ch.is_whitespace()).collect();
+        let size = SHAPES
+            .iter()
+            .map(|(r, c)| (r * c) as usize)
+            .find(|n| n * n == labels.len())
+            .ok_or(LayoutError::Length(labels.len()))?;
+        let mut names: Vec<char> = Vec::new();
+        let mut regions = Vec::with_capacity(labels.len());
+        for label in &labels {
+            if !names.contains(label) {
+                names.push(*label);
+            }
+            regions.push(names.iter().position(|n| n == label).unwrap() as u8 + 1);
+        }
+        if names.len() != size {
+            return Err(LayoutError::Regions {
+                expected: size,
+                found: names.len(),
+            });
+        }
+        for (idx, name) in names.iter().enumerate() {
+            let cells: Vec<usize> = (0..labels.len())
+                .filter(|cell| regions[*cell] as usize == idx + 1)
+                .collect();
+            if cells.len() != size {
+                return Err(LayoutError::RegionSize {
+                    region: *name,
+                    cells: cells.len(),
+                });
+            }
+            // flood the region from its first cell across shared edges
+            let mut reached = vec![cells[0]];
+            let mut next = 0;
+            while let Some(&cell) = reached.get(next) {
+                next += 1;
+                let (row, col) = (cell / size, cell % size);
+                let adjacent = [
+                    (row > 0).then(|| cell - size),
+                    (row + 1 < size).then(|| cell + size),
+                    (col > 0).then(|| cell - 1),
+                    (col + 1 < size).then(|| cell + 1),
+                ];
+                for cell in adjacent.into_iter().flatten() {
+                    if regions[cell] as usize == idx + 1 && !reached.contains(&cell) {
+                        reached.push(cell);
+                    }
+                }
+            }
+            if reached.len() != size {
+                return Err(LayoutError::Disconnected(*name));
+            }
+        }
+        Ok(Shape::with_regions(
+            size as u8,
+            None,
+            regions,
+            &[],
+            &[],
+            &[],
+        ))
+    }
+
+    fn with_regions(
+        size: u8,
+        boxes: Option<(u8, u8)>,
+        regions: Vec<u8>,
+        variants: &[Variant],
+        cages: &[Cage],
+        constraints: &[Arc<dyn Constraint>],
+    ) -> Shape {
+        let mut shape = Shape {
+            boxes,
+            size,
+            regions,
+            variants: variants.to_vec(),
+            cages: cages.to_vec(),
+            cage_houses: Vec::new(),
+            cell_cages: vec![None; size as usize * size as usize],
+            constraints: constraints.to_vec(),
+            cell_constraints: vec![Vec::new(); size as usize * size as usize],
+            houses: Vec::new(),
+            cell_houses: Vec::new(),
+            neighbours: Vec::new(),
+        };
+        let addrs: Vec<Addr> = shape.addrs().collect();
+        let idx = |a: &Addr| (a.row as usize - 1) * size as usize + a.col as usize - 1;
+        let house = |name: String, keep: &dyn Fn(&Addr) -> bool| House {
+            name,
+            cells: addrs.iter().filter(|addr| keep(addr)).copied().collect(),
+        };
+        for i in 1..=size {
+            shape.houses.push(house(format!("r{i}"), &|a| a.row == i));
+        }
+        for i in 1..=size {
+            shape.houses.push(house(format!("c{i}"), &|a| a.col == i));
+        }
+        for i in 1..=size {
+            let regions = &shape.regions;
+            shape
+                .houses
+                .push(house(format!("b{i}"), &|a| regions[idx(a)] == i));
+        }
+        if variants.contains(&Variant::Diagonal) {
+            shape
+                .houses
+                .push(house("d1".to_string(), &|a| a.row == a.col));
+            shape
+                .houses
+                .push(house("d2".to_string(), &|a| a.row + a.col == size + 1));
+        }
+        if variants.contains(&Variant::Hyper) {
+            // windows are box sized, one cell in from the edge and one apart
+            let side = boxes.expect("windows need square boxes").0;
+            let mut window = 1;
+            for top in (2..size)
+                .step_by(side as usize + 1)
+                .take((side - 1) as usize)
+            {
+                for left in (2..size)
+                    .step_by(side as usize + 1)
+                    .take((side - 1) as usize)
+                {
+                    shape.houses.push(house(format!("w{window}"), &|a| {
+                        (top..top + side).contains(&a.row) && (left..left + side).contains(&a.col)
+                    }));
+                    window += 1;
+                }
+            }
+        }
+        for (k, cage) in cages.iter().enumerate() {
+            let mut cells = cage.cells.clone();
+            cells.sort();
+            for addr in &cells {
+                shape.cell_cages[idx(addr)] = Some(k);
+            }
+            shape.cage_houses.push(shape.houses.len());
+            shape.houses.push(House {
+                name: format!("k{}", k + 1),
+                cells,
+            });
+        }
+        for (k, constraint) in constraints.iter().enumerate() {
+            for addr in constraint.cells() {
+                shape.cell_constraints[idx(&addr)].push(k);
+            }
+        }
+        let mut moves: Vec<(i32, i32)> = Vec::new();
+        if variants.contains(&Variant::AntiKing) {
+            moves.extend([(0, 1), (1, -1), (1, 0), (1, 1)]);
+        }
+        if variants.contains(&Variant::AntiKnight) {
+            moves.extend([(1, -2), (1, 2), (2, -1), (2, 1)]);
+        }
+
+        shape.cell_houses = vec![Vec::new(); addrs.len()];
+        for (h, house) in shape.houses.iter().enumerate() {
+            for addr in &house.cells {
+                shape.cell_houses[idx(addr)].push(h);
+            }
+        }
+        // a pair of cells a move apart is only a house if no other holds both
+        for a in &addrs {
+            for (dr, dc) in &moves {
+                let (row, col) = (a.row as i32 + dr, a.col as i32 + dc);
+                if row < 1 || row > size as i32 || col < 1 || col > size as i32 {
+                    continue;
+                }
+                let b = Addr {
+                    row: row as u8,
+                    col: col as u8,
+                };
+                let (ia, ib) = (idx(a), idx(&b));
+                if shape.cell_houses[ia]
+                    .iter()
+                    .any(|h| shape.cell_houses[ib].contains(h))
+                {
+                    continue;
+                }
+                let mut cells = vec![*a, b];
+                cells.sort();
+                let name = format!(
+                    "r{}c{}-r{}c{}",
+                    cells[0].row, cells[0].col, cells[1].row, cells[1].col
+                );
+                shape.cell_houses[ia].push(shape.houses.len());
+                shape.cell_houses[ib].push(shape.houses.len());
+                shape.houses.push(House { name, cells });
+            }
+        }
+
+        for (addr, houses) in addrs.iter().zip(&shape.cell_houses) {
+            let mut nghbrs: Vec<Addr> = houses
+                .iter()
+                .flat_map(|h| shape.houses[*h].cells.iter().copied())
+                .filter(|nghbr| nghbr != addr)
+                .collect();
+            nghbrs.sort();
+            nghbrs.dedup();
+            shape.neighbours.push(nghbrs);
+        }
+        shape
+    }
+
+    fn addrs(&self) -> impl Iterator<Item = Addr> {
+        let size = self.size;
+        (1..=size).flat_map(move |row| (1..=size).map(move |col| Addr { row, col }))
+    }
+
+    /// Index of `addr` in the row-major cell array.
+    fn idx(&self, addr: &Addr) -> usize {
+        (addr.row as usize - 1) * self.size as usize + addr.col as usize - 1
+    }
 }
 
 impl fmt::Display for Board {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{}", self.string())
+    }
+}
+
+/// Reason a board string could not be turned into a [`Board`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The string did not hold as many cells as any allowed board.
+    Length { expected: Vec<usize>, found: usize },
+    /// A character that is neither a cell nor a separator, at a zero-based
+    /// position in the string.
+    InvalidChar { pos: usize, ch: char },
+    /// A whitespace-separated cell of a board above 9x9 that is not a digit
+    /// of the board, starting at a zero-based position in the string.
+    InvalidToken { pos: usize, token: String },
+    /// Two givens with the same value share a house.
+    Conflict { a: Addr, b: Addr, val: u8 },
+    /// The region map of a jigsaw is not valid.
+    Layout(LayoutError),
+    /// The board cannot play by a variant, such as [`Variant::Hyper`]
+    /// without square boxes.
+    Variant(Variant),
+    /// The cages of a killer sudoku are not valid.
+    Cage(CageError),
+    /// The constraints on the digits are not valid.
+    Constraint(ConstraintError),
+}
+
+/// Reason a jigsaw layout could not be turned into regions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LayoutError {
+    /// The layout does not have the cells of a 4x4, 6x6, 9x9, 12x12, 16x16
+    /// or 25x25 board.
+    Length(usize),
+    /// The layout does not have one region per digit.
+    Regions { expected: usize, found: usize },
+    /// A region does not have one cell per digit.
+    RegionSize { region: char, cells: usize },
+    /// A region is split into parts that share no edge.
+    Disconnected(char),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::Length(len) => {
+                let sizes: Vec<String> = SHAPES
+                    .iter()
+                    .map(|(r, c)| format!("{0}x{0}", r * c))
+                    .collect();
+                let (last, rest) = sizes.split_last().unwrap();
+                write!(
+                    f,
+                    "{len} cells is not a {} or {last} board",
+                    rest.join(", ")
+                )
+            }
+            LayoutError::Regions { expected, found } => {
+                write!(f, "expected {expected} regions, found {found}")
+            }
+            LayoutError::RegionSize { region, cells } => {
+                write!(f, "region {region:?} has {cells} cells")
+            }
+            LayoutError::Disconnected(region) => write!(f, "region {region:?} is not connected"),
+        }
+    }
+}
+
+impl Error for LayoutError {}
+
+impl From<LayoutError> for ParseError {
+    fn from(e: LayoutError) -> ParseError {
+        ParseError::Layout(e)
+    }
+}
+
+impl From<CageError> for ParseError {
+    fn from(e: CageError) -> ParseError {
+        ParseError::Cage(e)
+    }
+}
+
+impl From<ConstraintError> for ParseError {
+    fn from(e: ConstraintError) -> ParseError {
+        ParseError::Constraint(e)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Length { expected, found } => {
+                let expected: Vec<String> = expected.iter().map(|n| n.to_string()).collect();
+                let expected = match expected.split_last() {
+                    Some((last, [])) => last.clone(),
+                    Some((last, rest)) => format!("{} or {last}", rest.join(", ")),
+                    None => String::new(),
+                };
+                write!(f, "expected {expected} cells, found {found}")
+            }
+            ParseError::InvalidChar { pos, ch } => {
+                write!(f, "invalid character {ch:?} at position {pos}")
+            }
+            ParseError::InvalidToken { pos, token } => {
+                write!(f, "invalid cell {token:?} at position {pos}")
+            }
+            ParseError::Conflict { a, b, val } => write!(
+                f,
+                "given {val} at r{}c{} conflicts with r{}c{}",
+                a.row, a.col, b.row, b.col
+            ),
+            ParseError::Layout(e) => write!(f, "invalid layout: {e}"),
+            ParseError::Variant(variant) => write!(f, "{variant:?} needs square boxes"),
+            ParseError::Cage(e) => write!(f, "invalid cages: {e}"),
+            ParseError::Constraint(e) => write!(f, "invalid constraints: {e}"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Character for digit `val`: `1` to `9`, then `A` for 10 up to `P` for 25,
+/// and `0` for an empty cell.
+pub fn symbol(val: u8) -> char {
+    match val {
+        0..=9 => char::from(b'0' + val),
+        _ => char::from(b'A' + val - 10),
+    }
+}
+
+/// Digit written as `ch`, the inverse of [`symbol`], in either case.
+fn digit(ch: char) -> Option<u8> {
+    match ch {
+        '1'..='9' => Some(ch as u8 - b'0'),
+        'A'..='Z' => Some(ch as u8 - b'A' + 10),
+        'a'..='z' => Some(ch as u8 - b'a' + 10),
+        '0' | '.' | '_' => Some(0),
+        _ => None,
     }
 }
 
 impl Board {
+    /// Build a board from a string of digits, `0` marking an empty cell.
+    ///
+    /// Panics if the string is not a valid board, see [`Board::parse`].
     pub fn new(board_string: &str) -> Board {
-        let mut idx: usize = 0;
-        let digits: Vec<u8> = board_string
-            .chars()
-            .map(|s| s.to_digit(10).expect("parse error") as u8)
+        Board::parse(board_string).unwrap_or_else(|e| panic!("parse error: {e}"))
+    }
+
+    /// Build a board from a string of cells, its size told by how many
+    /// there are: 4x4 and 6x6 with 2x2 and 2x3 boxes, 9x9, 12x12 with 3x4
+    /// boxes, 16x16 or 25x25.
+    ///
+    /// Empty cells may be written as `0`, `.` or `_`, and digits above 9 as
+    /// letters from `A`. Whitespace and the `|`, `-` and `+` grid lines are
+    /// ignored, so rows may be wrapped across lines and the output of
+    /// [`Board::string`] parses back to the same board. Boards above 9x9 may
+    /// instead separate their cells by whitespace and write digits as numbers,
+    /// `10` to `25`.
+    pub fn parse(board_string: &str) -> Result<Board, ParseError> {
+        let count = tokens(board_string).count();
+        let len = match SHAPES
+            .iter()
+            .any(|(r, c)| r * c > 9 && count == cell_count(*r, *c))
+        {
+            true => count,
+            false => board_string
+                .chars()
+                .filter(|ch| digit(*ch).is_some())
+                .count(),
+        };
+        match SHAPES.iter().find(|(r, c)| len == cell_count(*r, *c)) {
+            Some((box_rows, box_cols)) => Board::parse_with(board_string, *box_rows, *box_cols),
+            None => Err(ParseError::Length {
+                expected: SHAPES.iter().map(|(r, c)| cell_count(*r, *c)).collect(),
+                found: normalise(board_string, MAX_SIZE)?.len(),
+            }),
+        }
+    }
+
+    /// Build a board with boxes `box_rows` high and `box_cols` wide from a
+    /// string of cells, written as for [`Board::parse`].
+    ///
+    /// Panics if the boxes would give more than [`MAX_SIZE`] digits.
+    pub fn parse_with(board_string: &str, box_rows: u8, box_cols: u8) -> Result<Board, ParseError> {
+        Board::parse_shape(board_string, Shape::plain(box_rows, box_cols))
+    }
+
+    /// Build a jigsaw board from a string of cells, written as for
+    /// [`Board::parse`], and a layout string naming the region of each cell.
+    ///
+    /// The layout has one character per cell of a 4x4, 6x6, 9x9, 12x12,
+    /// 16x16 or 25x25 board, whitespace ignored, and any character may name
+    /// a region. There must be one region per digit,
+    /// each of one cell per digit and joined across the edges of its cells.
+    ///
+    /// ```text
+    /// 1122
+    /// 1322
+    /// 1344
+    /// 3344
+    /// ```
+    pub fn parse_jigsaw(board_string: &str, layout: &str) -> Result<Board, ParseError> {
+        Board::parse_shape(board_string, Arc::new(Shape::jigsaw(layout)?))
+    }
+
+    fn parse_shape(board_string: &str, shape: Arc<Shape>) -> Result<Board, ParseError> {
+        let (size, cells) = (shape.size, shape.size as usize * shape.size as usize);
+        let digits = match size > 9 && tokens(board_string).count() == cells {
+            true => normalise_tokens(board_string, size)?,
+            false => normalise(board_string, size)?,
+        };
+        if digits.len() != cells {
+            return Err(ParseError::Length {
+                expected: vec![cells],
+                found: digits.len(),
+            });
+        }
+
+        let cells = shape
+            .addrs()
+            .zip(digits)
+            .map(|(addr, val)| Cell::new(val, addr.row, addr.col))
             .collect();
-        let mut cells = HashMap::new();
-        for row in 1..10 {
-            for col in 1..10 {
-                let val = digits[idx];
-                let cell = Cell::new(val, row, col);
-                cells.insert(cell.addr, cell);
-                idx += 1;
+        Board::from_cells(shape, cells)
+    }
+
+    /// This board playing by `variants` as well as its rows, columns and
+    /// boxes, in place of any it had.
+    ///
+    /// Fails if the board has no square boxes for [`Variant::Hyper`], or if
+    /// two givens or set cells clash under the new rules.
+    pub fn with_variants(&self, variants: &[Variant]) -> Result<Board, ParseError> {
+        let square = matches!(self.boxes(), Some((rows, cols)) if rows == cols);
+        if variants.contains(&Variant::Hyper) && !square {
+            return Err(ParseError::Variant(Variant::Hyper));
+        }
+        if variants == self.variants() {
+            return Ok(self.clone());
+        }
+        let shape = Shape::with_regions(
+            self.size(),
+            self.boxes(),
+            self.shape.regions.clone(),
+            variants,
+            &self.shape.cages,
+            &self.shape.constraints,
+        );
+        Board::from_cells(Arc::new(shape), self.cells.clone())
+    }
+
+    /// Variants the board plays by.
+    pub fn variants(&self) -> &[Variant] {
+        &self.shape.variants
+    }
+
+    /// This board as a killer sudoku with `cages`, in place of any it had.
+    ///
+    /// Fails if a cage is empty, off the board, overlaps another or cannot
+    /// reach its sum, or if givens or set cells clash under the new rules.
+    /// The same cages as the board has give back the board, sharing its
+    /// layout.
+    pub fn with_cages(&self, cages: &[Cage]) -> Result<Board, ParseError> {
+        if cages == self.cages() {
+            return Ok(self.clone());
+        }
+        let mut seen = vec![false; self.cells.len()];
+        for (k, cage) in cages.iter().enumerate() {
+            if cage.cells.is_empty() {
+                return Err(CageError::Empty(k + 1).into());
+            }
+            for addr in &cage.cells {
+                if !(1..=self.size()).contains(&addr.row) || !(1..=self.size()).contains(&addr.col)
+                {
+                    return Err(CageError::OffBoard {
+                        cage: k + 1,
+                        addr: *addr,
+                    }
+                    .into());
+                }
+                if std::mem::replace(&mut seen[self.shape.idx(addr)], true) {
+                    return Err(CageError::Overlap(*addr).into());
+                }
             }
         }
-        let mut nhbrs = HashMap::new();
-        for (addr, cell) in &cells {
-            let cell_nhbrs = match <[Addr; 20]>::try_from(neighbours(&cell, &cells)) {
-                Ok(vec) => vec,
-                Err(o) => panic!("Could not create neighbours for {:?} {:?}", cell.addr, o),
-            };
-            nhbrs.insert(*addr, cell_nhbrs);
+        let shape = Shape::with_regions(
+            self.size(),
+            self.boxes(),
+            self.shape.regions.clone(),
+            &self.shape.variants,
+            cages,
+            &self.shape.constraints,
+        );
+        let board = Board::from_cells(Arc::new(shape), self.cells.clone())?;
+        for (k, cage) in cages.iter().enumerate() {
+            if board.cage_digits(k).is_none() {
+                return Err(CageError::Sum {
+                    cage: k + 1,
+                    sum: cage.sum,
+                }
+                .into());
+            }
+        }
+        Ok(board)
+    }
+
+    /// Cages of a killer sudoku, see [`Board::with_cages`].
+    pub fn cages(&self) -> &[Cage] {
+        &self.shape.cages
+    }
+
+    /// This board with `constraints` on its digits, in place of any it had.
+    ///
+    /// Fails if a constraint has a cell off the board, or if givens or set
+    /// cells break one.
+    pub fn with_constraints(
+        &self,
+        constraints: &[Arc<dyn Constraint>],
+    ) -> Result<Board, ParseError> {
+        let on_board = |v: u8| (1..=self.size()).contains(&v);
+        for (k, constraint) in constraints.iter().enumerate() {
+            for addr in constraint.cells() {
+                if !on_board(addr.row) || !on_board(addr.col) {
+                    return Err(ConstraintError::OffBoard {
+                        constraint: k + 1,
+                        addr,
+                    }
+                    .into());
+                }
+            }
+        }
+        let shape = Shape::with_regions(
+            self.size(),
+            self.boxes(),
+            self.shape.regions.clone(),
+            &self.shape.variants,
+            &self.shape.cages,
+            constraints,
+        );
+        let board = Board::from_cells(Arc::new(shape), self.cells.clone())?;
+        for (k, constraint) in constraints.iter().enumerate() {
+            if let Some(addr) = constraint.violations(&board).first() {
+                return Err(ConstraintError::Broken {
+                    constraint: k + 1,
+                    addr: *addr,
+                }
+                .into());
+            }
+        }
+        Ok(board)
+    }
+
+    /// Constraints on the digits, see [`Board::with_constraints`].
+    pub fn constraints(&self) -> &[Arc<dyn Constraint>] {
+        &self.shape.constraints
+    }
+
+    /// Index in [`Board::houses`] of the house of cage `k`.
+    pub fn cage_house(&self, k: usize) -> usize {
+        self.shape.cage_houses[k]
+    }
+
+    /// Digits the empty cells of cage `k` may still hold, for it to reach
+    /// its sum with different digits, or `None` if it cannot.
+    fn cage_digits(&self, k: usize) -> Option<u32> {
+        let cage = &self.shape.cages[k];
+        let used = self.used[self.shape.cage_houses[k]];
+        let avail = !used & ((1 << (self.size() + 1)) - 2);
+        let empty = cage.cells.len() - self.cage_filled[k];
+        let remaining = cage.sum.checked_sub(self.cage_sums[k])?;
+        cage::digits(avail, empty as u32, remaining)
+    }
+
+    /// Board of `cells` laid out as `shape`, checking no givens clash.
+    fn from_cells(shape: Arc<Shape>, cells: Box<[Cell]>) -> Result<Board, ParseError> {
+        let (houses, size) = (shape.houses.len(), shape.size as usize);
+        let mut board = Board {
+            cells,
+            used: vec![0; houses].into(),
+            counts: vec![0; houses * (size + 1)].into(),
+            cage_sums: vec![0; shape.cages.len()].into(),
+            cage_filled: vec![0; shape.cages.len()].into(),
+            shape,
+        };
+        for idx in 0..board.cells.len() {
+            let cell = board.cells[idx];
+            board.place(&cell.addr, cell.val);
         }
+        if let Some((a, b)) = board.conflicts().first() {
+            return Err(ParseError::Conflict {
+                a: *a,
+                b: *b,
+                val: board.get(a),
+            });
+        }
+        Ok(board)
+    }
 
-        return Board { cells, nhbrs };
+    /// Digits on the board, and cells along each side.
+    pub fn size(&self) -> u8 {
+        self.shape.size
     }
 
-    /// String representation of a Board.
+    /// Height and width of the boxes, `None` for a jigsaw.
+    pub fn boxes(&self) -> Option<(u8, u8)> {
+        self.shape.boxes
+    }
+
+    /// Every address on the board, in row-major order.
+    pub fn addrs(&self) -> impl Iterator<Item = Addr> {
+        self.shape.addrs()
+    }
+
+    /// Every house of the board, see [`House`].
+    pub fn houses(&self) -> &[House] {
+        &self.shape.houses
+    }
+
+    /// Indices into [`Board::houses`] of the houses holding `addr`.
+    pub fn houses_of(&self, addr: &Addr) -> &[usize] {
+        &self.shape.cell_houses[self.shape.idx(addr)]
+    }
+
+    /// Number of the box or jigsaw region holding `addr`, counting from 1
+    /// in row-major order of their first cells.
+    pub fn region(&self, addr: &Addr) -> u8 {
+        self.shape.regions[self.shape.idx(addr)]
+    }
+
+    /// String representation of a Board, with grid lines between boxes
+    /// unless it is a jigsaw.
     pub fn string(&self) -> String {
-        let mut cells = Vec::new();
-        let important_idx: [u8; 2] = [3, 6];
+        let (box_rows, box_cols) = self.boxes().unwrap_or((self.size(), self.size()));
+        let rule = vec!["-".repeat(box_cols as usize); (self.size() / box_cols) as usize];
         let mut s: String = String::from("\n");
-        for (_, cell) in &self.cells {
-            cells.push(cell);
-        }
-        // HashMap not sorted
-        cells.sort();
-        for cell in cells {
-            s.push_str(&cell.val.to_string());
-            if important_idx.contains(&cell.addr.col) {
-                s.push_str("|")
+        for cell in self.cells.iter() {
+            s.push(symbol(cell.val));
+            if cell.addr.col == self.size() {
+                s.push('\n');
+                if cell.addr.row % box_rows == 0 && cell.addr.row < self.size() {
+                    s.push_str(&rule.join("+"));
+                    s.push('\n');
+                }
+            } else if cell.addr.col % box_cols == 0 {
+                s.push('|')
             }
-            if cell.addr.col == 9 {
-                s.push_str("\n");
-                if important_idx.contains(&cell.addr.row) {
-                    s.push_str("---+---+---\n");
+        }
+        s
+    }
+
+    /// Board as a single line of cells, `0` marking an empty cell.
+    pub fn line(&self) -> String {
+        self.cells.iter().map(|cell| symbol(cell.val)).collect()
+    }
+
+    /// Every pair of neighbouring cells holding the same digit.
+    ///
+    /// Each pair is listed once, lowest address first, in row-major order.
+    pub fn conflicts(&self) -> Vec<(Addr, Addr)> {
+        let mut pairs = Vec::new();
+        for cell in self.cells.iter() {
+            let (addr, val) = (&cell.addr, cell.val);
+            if val == 0 {
+                continue;
+            }
+            for nghbr in self.neighbours(addr).iter().filter(|n| *n > addr) {
+                if self.get(nghbr) == val {
+                    pairs.push((*addr, *nghbr));
                 }
             }
         }
-        return s;
+        pairs
+    }
+
+    /// Whether every cell holds a digit.
+    pub fn is_complete(&self) -> bool {
+        self.cells.iter().all(|cell| cell.is_set())
+    }
+
+    /// Cells holding a digit that breaks one of the constraints, in
+    /// row-major order.
+    pub fn violations(&self) -> Vec<Addr> {
+        let mut addrs: Vec<Addr> = self
+            .shape
+            .constraints
+            .iter()
+            .flat_map(|c| c.violations(self))
+            .collect();
+        addrs.sort();
+        addrs.dedup();
+        addrs
+    }
+
+    /// Whether the board is complete, no neighbours share a digit, every
+    /// cage adds up to its sum and no constraint is broken.
+    pub fn is_solved(&self) -> bool {
+        self.is_complete()
+            && self.conflicts().is_empty()
+            && self.violations().is_empty()
+            && self
+                .shape
+                .cages
+                .iter()
+                .zip(self.cage_sums.iter())
+                .all(|(cage, sum)| cage.sum == *sum)
     }
 
     pub fn next_addr(&self, addr: &Addr) -> Addr {
-        if addr.col == 9 {
+        if addr.col == self.size() {
             return Addr {
                 row: addr.row + 1,
                 col: 1,
             };
         }
-        return Addr {
+        Addr {
             row: addr.row,
             col: addr.col + 1,
-        };
+        }
     }
 
     pub fn prev_addr(&self, addr: &Addr) -> Addr {
         if addr.col == 1 {
             return Addr {
                 row: addr.row - 1,
-                col: 9,
+                col: self.size(),
             };
         }
-        return Addr {
+        Addr {
             row: addr.row,
             col: addr.col - 1,
-        };
+        }
     }
 
-    pub fn neighbours(&self, addr: &Addr) -> &[Addr; 20] {
-        return self.nhbrs.get(addr).expect("No addr {addr:?}");
+    /// Cells sharing a house with `addr`, in row-major order.
+    pub fn neighbours(&self, addr: &Addr) -> &[Addr] {
+        &self.shape.neighbours[self.shape.idx(addr)]
     }
 
     pub fn legal_values(&self, addr: &Addr) -> Vec<u8> {
-        if !self.can_set(addr) {
-            let cell = &self.cells.get(addr).expect("No addr {nghbr:?}");
-            return vec![cell.val];
-        }
-        let mut vals = vec![1, 2, 3, 4, 5, 6, 7, 8, 9];
-        for nghbr in self.neighbours(addr) {
-            let cell = &self.cells.get(nghbr).expect("No addr {nghbr:?}");
-            if vals.contains(&cell.val) {
-                vals.remove(
-                    vals.binary_search(&cell.val)
-                        .expect("No value {cell.val:?} at addr {addr:?}"),
-                );
-            }
+        let mask = self.candidates(addr);
+        (1..=self.size()).filter(|d| mask & (1 << d) != 0).collect()
+    }
+
+    /// Legal values at `addr` as a bitmask, bit `d` standing for digit `d`.
+    pub fn candidates(&self, addr: &Addr) -> u32 {
+        let cell = &self.cells[self.shape.idx(addr)];
+        if !cell.can_set() {
+            return 1 << cell.val;
         }
-        return vals;
+        let used = self.shape.cell_houses[self.shape.idx(addr)]
+            .iter()
+            .fold(0, |used, house| used | self.used[*house]);
+        let digits = (1 << (self.size() + 1)) - 2;
+        let cage = match self.shape.cell_cages[self.shape.idx(addr)] {
+            Some(k) => self.cage_digits(k).unwrap_or(0),
+            None => digits,
+        };
+        self.shape.cell_constraints[self.shape.idx(addr)]
+            .iter()
+            .fold(!used & digits & cage, |mask, k| {
+                mask & self.shape.constraints[*k].candidates(self, addr)
+            })
+    }
+
+    /// Value at `addr`, `0` if the cell is empty.
+    pub fn get(&self, addr: &Addr) -> u8 {
+        self.cells[self.shape.idx(addr)].val
     }
 
     pub fn can_set(&self, addr: &Addr) -> bool {
-        let cell = *self.cells.get(addr).expect("No addr {addr:?}");
-        return cell.can_set();
+        self.cells[self.shape.idx(addr)].can_set()
     }
 
     pub fn set(&mut self, addr: &Addr, val: u8) {
-        let mut cell = *self.cells.get(addr).expect("No addr {addr:?}");
         if !self.can_set(addr) {
             return;
         }
-        cell.set(val);
-        self.cells.insert(*addr, cell);
+        self.unset(addr);
+        let idx = self.shape.idx(addr);
+        self.cells[idx].set(val);
+        self.place(addr, val);
     }
 
     pub fn unset(&mut self, addr: &Addr) {
-        let mut cell = *self.cells.get(addr).expect("No addr {addr:?}");
+        let idx = self.shape.idx(addr);
+        let cell = &mut self.cells[idx];
+        if !cell.can_set() || !cell.is_set() {
+            return;
+        }
+        let val = cell.val;
         cell.unset();
-        self.cells.insert(*addr, cell);
+        if let Some(k) = self.shape.cell_cages[idx] {
+            self.cage_sums[k] -= val as u32;
+            self.cage_filled[k] -= 1;
+        }
+        let digits = self.shape.size as usize + 1;
+        for &house in &self.shape.cell_houses[idx] {
+            let count = &mut self.counts[house * digits + val as usize];
+            *count -= 1;
+            if *count == 0 {
+                self.used[house] &= !(1 << val);
+            }
+        }
     }
-}
 
-fn neighbours(cell: &Cell, cells: &HashMap<Addr, Cell>) -> Vec<Addr> {
-    let mut nghs = Vec::new();
-    for (addr, friend) in cells {
-        if cell.addr == *addr {
-            continue;
+    /// Mark `val` as used in the houses of `addr`.
+    fn place(&mut self, addr: &Addr, val: u8) {
+        if val == 0 {
+            return;
+        }
+        let idx = self.shape.idx(addr);
+        if let Some(k) = self.shape.cell_cages[idx] {
+            self.cage_sums[k] += val as u32;
+            self.cage_filled[k] += 1;
         }
-        let shared_col = cell.addr.col == friend.addr.col;
-        let shared_row = cell.addr.row == friend.addr.row;
-        let shared_sqr =
-            sqr_idx(cell.addr.col, cell.addr.row) == sqr_idx(friend.addr.col, friend.addr.row);
-        if shared_row | shared_col | shared_sqr {
-            nghs.push(*addr)
+        let digits = self.shape.size as usize + 1;
+        for &house in &self.shape.cell_houses[idx] {
+            self.counts[house * digits + val as usize] += 1;
+            self.used[house] |= 1 << val;
         }
     }
-    return nghs;
 }
 
-fn sqr_idx(col: u8, row: u8) -> u8 {
-    return (col - 1) / 3 + 3 * ((row - 1) / 3) + 1;
+/// Whitespace-separated words of a board string that are not grid lines,
+/// with their positions.
+fn tokens(board_string: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut pos = 0;
+    board_string
+        .split(char::is_whitespace)
+        .map(move |token| {
+            let start = pos;
+            pos += token.chars().count() + 1;
+            (start, token)
+        })
+        .filter(|(_, token)| !token.is_empty() && !token.chars().all(|ch| "|-+".contains(ch)))
+}
+
+/// Cell values of a board string, one per character, skipping whitespace
+/// and grid lines.
+fn normalise(board_string: &str, size: u8) -> Result<Vec<u8>, ParseError> {
+    let mut digits = Vec::with_capacity(size as usize * size as usize);
+    for (pos, ch) in board_string.chars().enumerate() {
+        match digit(ch) {
+            Some(val) if val <= size => digits.push(val),
+            _ if "|-+".contains(ch) || ch.is_whitespace() => continue,
+            _ => return Err(ParseError::InvalidChar { pos, ch }),
+        }
+    }
+    Ok(digits)
+}
+
+/// Cell values of a board string, one per whitespace-separated word.
+fn normalise_tokens(board_string: &str, size: u8) -> Result<Vec<u8>, ParseError> {
+    let mut digits = Vec::with_capacity(size as usize * size as usize);
+    for (pos, token) in tokens(board_string) {
+        let mut chars = token.chars();
+        let val = match (chars.next(), chars.next()) {
+            (Some(ch), None) => digit(ch),
+            _ => token.parse().ok(),
+        };
+        match val {
+            Some(val) if val <= size => digits.push(val),
+            _ => {
+                return Err(ParseError::InvalidToken {
+                    pos,
+                    token: token.to_string(),
+                })
+            }
+        }
+    }
+    Ok(digits)
 }
 
 #[cfg(test)]
@@ -256,8 +1111,16 @@ mod tests {
         000|419|005\n\
         000|080|079\n\
         ";
-        let board = Board::new(&BOARD_STRING);
+        let board = Board::new(BOARD_STRING);
         assert_eq!(board.string(), exp);
+
+        // clones share the layout of every plain board of the same size
+        let mut copy = board.clone();
+        copy.set(&Addr { row: 1, col: 3 }, 4);
+        assert_eq!(board.get(&Addr { row: 1, col: 3 }), 0);
+        assert!(Arc::ptr_eq(&copy.shape, &Board::new(BOARD_STRING).shape));
+        let same = board.with_cages(&[]).unwrap();
+        assert!(Arc::ptr_eq(&same.shape, &board.shape));
     }
 
     #[test]
@@ -284,15 +1147,14 @@ mod tests {
             Addr { row: 8, col: 1 },
             Addr { row: 9, col: 1 },
         ];
-        let board = Board::new(&BOARD_STRING);
-        let mut got = *board.neighbours(&Addr { row: 1, col: 1 });
-        got.sort();
-        assert_eq!(got.iter().eq(exp.iter()), true);
+        let board = Board::new(BOARD_STRING);
+        let got = board.neighbours(&Addr { row: 1, col: 1 });
+        assert!(got.iter().eq(exp.iter()));
     }
 
     #[test]
     fn test_next_addr() {
-        let board = Board::new(&BOARD_STRING);
+        let board = Board::new(BOARD_STRING);
 
         let mut got = board.next_addr(&Addr { row: 1, col: 1 });
         assert_eq!(got, Addr { row: 1, col: 2 });
@@ -306,7 +1168,7 @@ mod tests {
 
     #[test]
     fn test_prev_addr() {
-        let board = Board::new(&BOARD_STRING);
+        let board = Board::new(BOARD_STRING);
 
         let mut got = board.prev_addr(&Addr { row: 9, col: 9 });
         assert_eq!(got, Addr { row: 9, col: 8 });
@@ -317,7 +1179,7 @@ mod tests {
 
     #[test]
     fn test_legal_values() {
-        let mut board = Board::new(&BOARD_STRING);
+        let mut board = Board::new(BOARD_STRING);
 
         let mut got = board.legal_values(&Addr { row: 1, col: 3 });
         assert_eq!(got, vec![1, 2, 4]);
@@ -332,4 +1194,503 @@ mod tests {
         got = board.legal_values(&Addr { row: 1, col: 3 });
         assert_eq!(got, vec![1, 2, 4]);
     }
+
+    #[test]
+    fn test_parse_errors() {
+        let got = Board::parse("53007");
+        assert_eq!(
+            got.err(),
+            Some(ParseError::Length {
+                expected: vec![16, 36, 81, 144, 256, 625],
+                found: 5
+            })
+        );
+        assert_eq!(
+            Board::parse_with("1234", 3, 3).err().unwrap().to_string(),
+            "expected 81 cells, found 4"
+        );
+
+        let bad = BOARD_STRING.replacen('7', "x", 1);
+        let got = Board::parse(&bad);
+        assert_eq!(got.err(), Some(ParseError::InvalidChar { pos: 4, ch: 'x' }));
+
+        // 5 at r1c1 is repeated at r1c3
+        let clash = BOARD_STRING.replacen("530", "535", 1);
+        let got = Board::parse(&clash);
+        assert_eq!(
+            got.err(),
+            Some(ParseError::Conflict {
+                a: Addr { row: 1, col: 1 },
+                b: Addr { row: 1, col: 3 },
+                val: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_notations() {
+        let board = Board::new(BOARD_STRING);
+
+        let dots = BOARD_STRING.replace('0', ".");
+        assert_eq!(Board::new(&dots).string(), board.string());
+
+        let underscores = BOARD_STRING.replace('0', "_");
+        assert_eq!(Board::new(&underscores).string(), board.string());
+
+        let wrapped = "\
+        5 3 . . 7 . . . .
+        6 . . 1 9 5 . . .
+        . 9 8 . . . . 6 .
+        8 . . . 6 . . . 3
+        4 . . 8 . 3 . . 1
+        7 . . . 2 . . . 6
+        . 6 . . . . 2 8 .
+        . . . 4 1 9 . . 5
+        . . . . 8 . . 7 9
+        ";
+        assert_eq!(Board::new(wrapped).string(), board.string());
+
+        let round_trip = Board::new(&board.string());
+        assert_eq!(round_trip.string(), board.string());
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let mut board = Board::new(BOARD_STRING);
+        assert!(board.conflicts().is_empty());
+        assert!(!board.is_complete());
+        assert!(!board.is_solved());
+
+        // 5 is already in row 2 and in the top left box
+        board.set(&Addr { row: 2, col: 2 }, 5);
+        assert_eq!(
+            board.conflicts(),
+            vec![
+                (Addr { row: 1, col: 1 }, Addr { row: 2, col: 2 }),
+                (Addr { row: 2, col: 2 }, Addr { row: 2, col: 6 }),
+            ]
+        );
+
+        // 9 is already in row 2 and in the top left box
+        board.set(&Addr { row: 2, col: 3 }, 9);
+        assert_eq!(
+            board.conflicts(),
+            vec![
+                (Addr { row: 1, col: 1 }, Addr { row: 2, col: 2 }),
+                (Addr { row: 2, col: 2 }, Addr { row: 2, col: 6 }),
+                (Addr { row: 2, col: 3 }, Addr { row: 2, col: 5 }),
+                (Addr { row: 2, col: 3 }, Addr { row: 3, col: 2 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_solved() {
+        let solved = "\
+        534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+        let board = Board::new(solved);
+        assert!(board.is_complete());
+        assert!(board.is_solved());
+
+        let mut board = Board::new(&solved.replacen('5', "0", 1));
+        board.set(&Addr { row: 1, col: 1 }, 3);
+        assert!(board.is_complete());
+        assert!(!board.is_solved());
+    }
+
+    #[test]
+    fn test_candidates() {
+        let mut board = Board::new(BOARD_STRING);
+        let addr = Addr { row: 1, col: 3 };
+        assert_eq!(board.candidates(&addr), 0b10110);
+
+        // a given only allows its own value
+        assert_eq!(board.candidates(&Addr { row: 1, col: 1 }), 1 << 5);
+
+        // two clashing 4s, unsetting one keeps 4 out of the shared box
+        board.set(&Addr { row: 2, col: 2 }, 4);
+        board.set(&Addr { row: 3, col: 1 }, 4);
+        board.unset(&Addr { row: 2, col: 2 });
+        assert_eq!(board.candidates(&addr), 0b110);
+        board.unset(&Addr { row: 3, col: 1 });
+        assert_eq!(board.candidates(&addr), 0b10110);
+    }
+
+    #[test]
+    fn test_small_boards() {
+        let board = Board::new("1200 0010 0100 0021");
+        assert_eq!((board.size(), board.boxes()), (4, Some((2, 2))));
+        assert_eq!(board.string(), "\n12|00\n00|10\n--+--\n01|00\n00|21\n");
+        assert_eq!(board.legal_values(&Addr { row: 1, col: 3 }), vec![3, 4]);
+        assert_eq!(board.neighbours(&Addr { row: 1, col: 1 }).len(), 7);
+
+        // 6x6 boxes are two rows high and three columns wide
+        let board = Board::new(&"0".repeat(36));
+        assert_eq!(board.boxes(), Some((2, 3)));
+        assert_eq!(board.region(&Addr { row: 2, col: 4 }), 2);
+        assert_eq!(board.region(&Addr { row: 3, col: 1 }), 3);
+        assert_eq!(board.string().lines().nth(3), Some("---+---"));
+        assert_eq!(
+            board.legal_values(&Addr { row: 6, col: 6 }),
+            vec![1, 2, 3, 4, 5, 6]
+        );
+
+        // or three high and two wide, given explicitly
+        let board = Board::parse_with(&"0".repeat(36), 3, 2).unwrap();
+        assert_eq!(board.region(&Addr { row: 3, col: 3 }), 2);
+        assert!(Board::parse("7".repeat(16).as_str()).is_err());
+    }
+
+    #[test]
+    fn test_large_boards() {
+        let mut board = Board::new(&"0".repeat(256));
+        assert_eq!((board.size(), board.boxes()), (16, Some((4, 4))));
+        let addr = Addr { row: 16, col: 16 };
+        assert_eq!(board.candidates(&addr), 0x1_fffe);
+        board.set(&Addr { row: 16, col: 1 }, 16);
+        board.set(&Addr { row: 13, col: 13 }, 10);
+        assert_eq!(board.candidates(&addr), 0x0_fbfe);
+        assert_eq!(board.line().chars().nth(255 - 15), Some('G'));
+        assert_eq!(board.neighbours(&addr).len(), 39);
+
+        // letters and numbered cells read the same board
+        let letters = board.line();
+        let numbers: Vec<String> = letters
+            .chars()
+            .map(|ch| match ch {
+                'A' => "10".to_string(),
+                'G' => "16".to_string(),
+                _ => ch.to_string(),
+            })
+            .collect();
+        assert_eq!(Board::new(&numbers.join(" ")).line(), letters);
+        assert_eq!(Board::new(&letters.to_lowercase()).line(), letters);
+        assert_eq!(Board::new(&board.string()).line(), letters);
+
+        let mut bad = numbers.clone();
+        bad[3] = "17".to_string();
+        assert_eq!(
+            Board::parse(&bad.join(" ")).err(),
+            Some(ParseError::InvalidToken {
+                pos: 6,
+                token: "17".to_string()
+            })
+        );
+
+        let board = Board::new(&"0".repeat(625));
+        assert_eq!(board.size(), 25);
+        assert_eq!(board.legal_values(&addr).last(), Some(&25));
+        assert_eq!(symbol(25), 'P');
+    }
+
+    const LAYOUT: &str = "\
+    112222333\
+    111122333\
+    111222333\
+    444555666\
+    444556666\
+    444555566\
+    777888999\
+    777888999\
+    777888999\
+    ";
+
+    #[test]
+    fn test_jigsaw() {
+        let board = Board::parse_jigsaw(&"0".repeat(81), LAYOUT).unwrap();
+        assert_eq!(board.boxes(), None);
+        assert_eq!(board.region(&Addr { row: 1, col: 3 }), 2);
+        assert_eq!(board.region(&Addr { row: 2, col: 4 }), 1);
+        let nhbrs = board.neighbours(&Addr { row: 1, col: 3 });
+        // only three of its region share its row, so it has 21 neighbours
+        assert_eq!(nhbrs.len(), 21);
+        assert!(nhbrs.contains(&Addr { row: 2, col: 5 }));
+        assert!(!nhbrs.contains(&Addr { row: 2, col: 2 }));
+        assert_eq!(board.string().lines().nth(1), Some("000000000"));
+
+        // r1c3 and r2c5 share a region but not a box
+        let mut givens = "0".repeat(81);
+        givens.replace_range(2..3, "5");
+        givens.replace_range(13..14, "5");
+        assert!(Board::new(&givens).conflicts().is_empty());
+        assert_eq!(
+            Board::parse_jigsaw(&givens, LAYOUT).err(),
+            Some(ParseError::Conflict {
+                a: Addr { row: 1, col: 3 },
+                b: Addr { row: 2, col: 5 },
+                val: 5
+            })
+        );
+        let mut board = Board::parse_jigsaw(&"0".repeat(81), LAYOUT).unwrap();
+        board.set(&Addr { row: 1, col: 3 }, 5);
+        assert!(!board.legal_values(&Addr { row: 2, col: 5 }).contains(&5));
+        assert!(board.legal_values(&Addr { row: 2, col: 2 }).contains(&5));
+    }
+
+    #[test]
+    fn test_layout_errors() {
+        let blank = "0".repeat(81);
+        let err = |layout: &str| Board::parse_jigsaw(&blank, layout).err();
+        assert_eq!(
+            err("11223"),
+            Some(ParseError::Layout(LayoutError::Length(5)))
+        );
+        // square, but no size the solvers know
+        assert_eq!(err("1"), Some(ParseError::Layout(LayoutError::Length(1))));
+        assert_eq!(
+            err(&"1".repeat(49)),
+            Some(ParseError::Layout(LayoutError::Length(49)))
+        );
+        assert_eq!(
+            LayoutError::Length(49).to_string(),
+            "49 cells is not a 4x4, 6x6, 9x9, 12x12, 16x16 or 25x25 board"
+        );
+        assert_eq!(
+            err(&LAYOUT.replace('9', "8")),
+            Some(ParseError::Layout(LayoutError::Regions {
+                expected: 9,
+                found: 8
+            }))
+        );
+        assert_eq!(
+            err(&LAYOUT.replacen('2', "1", 1)),
+            Some(ParseError::Layout(LayoutError::RegionSize {
+                region: '1',
+                cells: 10
+            }))
+        );
+        // swap r1c1 of region 1 with r9c9 of region 9
+        let split = format!("9{}1", &LAYOUT[1..80]);
+        assert_eq!(
+            err(&split),
+            Some(ParseError::Layout(LayoutError::Disconnected('9')))
+        );
+        assert_eq!(
+            ParseError::Layout(LayoutError::Disconnected('9')).to_string(),
+            "invalid layout: region '9' is not connected"
+        );
+    }
+
+    #[test]
+    fn test_variants() {
+        let blank = Board::new(&"0".repeat(81));
+        let count = |variant: Variant, row: u8, col: u8| {
+            let board = blank.with_variants(&[variant]).unwrap();
+            board.neighbours(&Addr { row, col }).len()
+        };
+        // r4c4 to r9c9 of the diagonal are outside r1c1's row, column and box
+        assert_eq!(count(Variant::Diagonal, 1, 1), 26);
+        assert_eq!(count(Variant::Diagonal, 1, 2), 20);
+        // r2c2 is in the top left window, which adds r3c4, r4c3 and r4c4
+        assert_eq!(count(Variant::Hyper, 2, 2), 23);
+        assert_eq!(count(Variant::AntiKing, 3, 3), 23);
+        assert_eq!(count(Variant::AntiKing, 5, 5), 20);
+        assert_eq!(count(Variant::AntiKnight, 5, 5), 28);
+        assert_eq!(count(Variant::AntiKnight, 1, 1), 20);
+
+        let board = blank
+            .with_variants(&[Variant::Diagonal, Variant::Hyper])
+            .unwrap();
+        assert_eq!(board.variants(), &[Variant::Diagonal, Variant::Hyper]);
+        let names: Vec<&str> = board.houses()[27..]
+            .iter()
+            .map(|h| h.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["d1", "d2", "w1", "w2", "w3", "w4"]);
+        assert_eq!(board.houses()[31].cells[0], Addr { row: 6, col: 2 });
+
+        let mut board = blank.with_variants(&[Variant::AntiKnight]).unwrap();
+        let addr = Addr { row: 5, col: 5 };
+        board.set(&Addr { row: 3, col: 4 }, 7);
+        assert!(!board.legal_values(&addr).contains(&7));
+        assert_eq!(board.houses_of(&addr).len(), 3 + 8);
+        assert_eq!(board.houses()[board.houses_of(&addr)[3]].name, "r3c4-r5c5");
+        board.set(&addr, 7);
+        assert_eq!(board.conflicts(), vec![(Addr { row: 3, col: 4 }, addr)]);
+
+        // a 5 at r5c5 only clashes with r1c1 on the diagonal
+        let givens = BOARD_STRING.replacen("400803001", "400853001", 1);
+        let board = Board::new(&givens);
+        assert_eq!(
+            board.with_variants(&[Variant::Diagonal]).err(),
+            Some(ParseError::Conflict {
+                a: Addr { row: 1, col: 1 },
+                b: Addr { row: 5, col: 5 },
+                val: 5
+            })
+        );
+        assert!(board.with_variants(&[]).is_ok());
+
+        // windows only fit between square boxes
+        let hyper = |board: Board| board.with_variants(&[Variant::Hyper]).err();
+        assert_eq!(
+            hyper(Board::new(&"0".repeat(36))),
+            Some(ParseError::Variant(Variant::Hyper))
+        );
+        assert_eq!(
+            hyper(Board::parse_jigsaw(&"0".repeat(81), LAYOUT).unwrap()),
+            Some(ParseError::Variant(Variant::Hyper))
+        );
+        assert_eq!(hyper(Board::new(&"0".repeat(256))), None);
+        assert_eq!(
+            ParseError::Variant(Variant::Hyper).to_string(),
+            "Hyper needs square boxes"
+        );
+    }
+
+    #[test]
+    fn test_cages() {
+        // the 4x4 killer with the unique solution 1234 3412 2143 4321
+        let cages = cage::parse(
+            "6 r1c1 r2c1 r3c1\n7 r1c2 r2c2 r2c3\n7 r1c3 r1c4\n\
+             5 r2c4 r3c4\n8 r3c2 r4c2 r4c1\n7 r3c3 r4c3 r4c4\n",
+        )
+        .unwrap();
+        let blank = Board::new(&"0".repeat(16));
+        let mut board = blank.with_cages(&cages).unwrap();
+        assert_eq!(board.cages(), &cages[..]);
+        assert_eq!(board.houses()[board.cage_house(5)].name, "k6");
+        // 6 in three cells is 1 + 2 + 3, 7 in two is 3 + 4
+        assert_eq!(board.candidates(&Addr { row: 1, col: 1 }), 0b1110);
+        assert_eq!(board.candidates(&Addr { row: 1, col: 3 }), 0b11000);
+        board.set(&Addr { row: 2, col: 4 }, 1);
+        assert_eq!(board.candidates(&Addr { row: 3, col: 4 }), 0b10000);
+        // a 1 in the 7 cage would need a 6 beside it
+        board.set(&Addr { row: 1, col: 3 }, 1);
+        assert_eq!(board.candidates(&Addr { row: 1, col: 4 }), 0);
+        board.unset(&Addr { row: 1, col: 3 });
+        assert_eq!(board.candidates(&Addr { row: 1, col: 4 }), 0b11000);
+
+        // a valid grid is not solved unless the cages add up
+        let mut board = blank.with_cages(&cages).unwrap();
+        let swapped = Board::new("4231 3142 2413 1324");
+        for addr in blank.addrs() {
+            board.set(&addr, swapped.get(&addr));
+        }
+        assert!(board.is_complete() && board.conflicts().is_empty());
+        assert!(!board.is_solved());
+        assert!(swapped.is_solved());
+        assert!(Board::new("1234 3412 2143 4321")
+            .with_cages(&cages)
+            .unwrap()
+            .is_solved());
+
+        let cage = |sum: u32, cells: &[(u8, u8)]| Cage {
+            sum,
+            cells: cells.iter().map(|&(row, col)| Addr { row, col }).collect(),
+        };
+        let err = |cages: &[Cage]| blank.with_cages(cages).err();
+        assert_eq!(err(&[cage(3, &[])]), Some(CageError::Empty(1).into()));
+        assert_eq!(
+            err(&[cage(3, &[(1, 1)]), cage(3, &[(1, 2), (5, 1)])]),
+            Some(
+                CageError::OffBoard {
+                    cage: 2,
+                    addr: Addr { row: 5, col: 1 }
+                }
+                .into()
+            )
+        );
+        assert_eq!(
+            err(&[cage(3, &[(1, 1), (1, 2)]), cage(3, &[(1, 2), (2, 2)])]),
+            Some(CageError::Overlap(Addr { row: 1, col: 2 }).into())
+        );
+        assert_eq!(
+            err(&[cage(8, &[(1, 1), (1, 2)])]),
+            Some(CageError::Sum { cage: 1, sum: 8 }.into())
+        );
+        assert_eq!(
+            swapped.with_cages(&cages).err(),
+            Some(CageError::Sum { cage: 1, sum: 6 }.into())
+        );
+        // givens in different rows, columns and boxes may still share a cage
+        let board = Board::new("1000 0010 0000 0000");
+        assert_eq!(
+            board.with_cages(&[cage(2, &[(1, 1), (2, 3)])]).err(),
+            Some(ParseError::Conflict {
+                a: Addr { row: 1, col: 1 },
+                b: Addr { row: 2, col: 3 },
+                val: 1
+            })
+        );
+        assert_eq!(
+            board.with_variants(&[Variant::Diagonal]).unwrap().cages(),
+            &[]
+        );
+        let board = blank.with_cages(&cages).unwrap();
+        assert_eq!(board.with_variants(&[]).unwrap().cages(), &cages[..]);
+    }
+
+    #[test]
+    fn test_constraints() {
+        use constraint::{Dot, Kropki, Thermometer};
+
+        let blank = Board::new(&"0".repeat(81));
+        let thermo: Arc<dyn Constraint> = Arc::new(Thermometer {
+            cells: vec![Addr { row: 1, col: 1 }, Addr { row: 1, col: 2 }],
+        });
+        let dot: Arc<dyn Constraint> = Arc::new(Kropki {
+            a: Addr { row: 1, col: 2 },
+            b: Addr { row: 2, col: 2 },
+            dot: Dot::Black,
+        });
+        let mut board = blank
+            .with_constraints(&[thermo.clone(), dot.clone()])
+            .unwrap();
+        assert_eq!(board.constraints().len(), 2);
+        // r1c2 is above r1c1 and on a black dot
+        assert_eq!(
+            board.legal_values(&Addr { row: 1, col: 2 }),
+            vec![2, 3, 4, 6, 8]
+        );
+        board.set(&Addr { row: 2, col: 2 }, 3);
+        assert_eq!(board.legal_values(&Addr { row: 1, col: 2 }), vec![6]);
+        board.set(&Addr { row: 1, col: 1 }, 7);
+        assert!(board.legal_values(&Addr { row: 1, col: 2 }).is_empty());
+        board.set(&Addr { row: 1, col: 2 }, 6);
+        assert!(board.conflicts().is_empty());
+        assert_eq!(
+            board.violations(),
+            vec![Addr { row: 1, col: 1 }, Addr { row: 1, col: 2 }]
+        );
+
+        let solved = Board::new(
+            "534678912672195348198342567859761423426853791713924856961537284287419635345286179",
+        );
+        let thermo: Arc<dyn Constraint> = Arc::new(Thermometer {
+            cells: vec![Addr { row: 1, col: 2 }, Addr { row: 1, col: 4 }],
+        });
+        let board = solved
+            .with_constraints(std::slice::from_ref(&thermo))
+            .unwrap();
+        assert!(board.is_solved());
+        assert_eq!(board.with_variants(&[]).unwrap().constraints().len(), 1);
+        assert_eq!(board.with_cages(&[]).unwrap().constraints().len(), 1);
+
+        assert_eq!(
+            solved.with_constraints(&[dot]).err(),
+            Some(ParseError::Constraint(ConstraintError::Broken {
+                constraint: 1,
+                addr: Addr { row: 1, col: 2 }
+            }))
+        );
+        let off: Arc<dyn Constraint> = Arc::new(Thermometer {
+            cells: vec![Addr { row: 9, col: 9 }, Addr { row: 9, col: 10 }],
+        });
+        assert_eq!(
+            blank.with_constraints(&[thermo, off]).err(),
+            Some(ParseError::Constraint(ConstraintError::OffBoard {
+                constraint: 2,
+                addr: Addr { row: 9, col: 10 }
+            }))
+        );
+        assert_eq!(
+            ParseError::Constraint(ConstraintError::OffBoard {
+                constraint: 2,
+                addr: Addr { row: 9, col: 10 }
+            })
+            .to_string(),
+            "invalid constraints: constraint 2 has r9c10 off the board"
+        );
+    }
 }
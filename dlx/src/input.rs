@@ -0,0 +1,210 @@
+//! Problems in the text format of Knuth's DLX programs.
+//!
+//! The first line names the columns, with primary columns before a `|` and
+//! secondary columns after it. Every later line is one option, a row of the
+//! matrix, naming the columns it covers. A secondary column may be given a
+//! colour as `name:colour`, and options that agree on the colour can share
+//! it. Blank lines and lines starting with `|` are comments.
+//!
+//! ```text
+//! | two queens on a 2x2 board, diagonals secondary
+//! r1 r2 c1 c2 | a b
+//! r1 c1 a
+//! r1 c2 b
+//! r2 c1 b
+//! r2 c2 a
+//! ```
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+use crate::{Builder, A};
+
+/// Reason a problem could not be read, with one-based line numbers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// There was no line of column names.
+    NoColumns,
+    /// The column line has more than one `|`.
+    ExtraSeparator,
+    /// A column is named twice in the column line.
+    DuplicateColumn(String),
+    /// An option names a column not in the column line.
+    UnknownColumn { line: usize, name: String },
+    /// An option names the same column twice.
+    RepeatedColumn { line: usize, name: String },
+    /// An option gives a primary column a colour.
+    PrimaryColor { line: usize, name: String },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::NoColumns => write!(f, "no column names"),
+            ParseError::ExtraSeparator => write!(f, "more than one | in the column names"),
+            ParseError::DuplicateColumn(name) => write!(f, "column {name:?} named twice"),
+            ParseError::UnknownColumn { line, name } => {
+                write!(f, "unknown column {name:?} on line {line}")
+            }
+            ParseError::RepeatedColumn { line, name } => {
+                write!(f, "column {name:?} repeated on line {line}")
+            }
+            ParseError::PrimaryColor { line, name } => {
+                write!(f, "primary column {name:?} given a colour on line {line}")
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Read a problem, its rows numbered in the order the options appear.
+pub fn parse(text: &str) -> Result<A, ParseError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('|'));
+
+    let (_, columns) = lines.next().ok_or(ParseError::NoColumns)?;
+    let mut parts = columns.split('|');
+    let primary: Vec<&str> = parts.next().unwrap_or("").split_whitespace().collect();
+    let secondary: Vec<&str> = parts.next().unwrap_or("").split_whitespace().collect();
+    if parts.next().is_some() {
+        return Err(ParseError::ExtraSeparator);
+    }
+    let names: Vec<&str> = primary.iter().chain(secondary.iter()).copied().collect();
+    let mut seen = HashSet::new();
+    if let Some(name) = names.iter().find(|name| !seen.insert(**name)) {
+        return Err(ParseError::DuplicateColumn(name.to_string()));
+    }
+
+    let mut builder = Builder::with_names(&names);
+    let secondary: Vec<usize> = (primary.len()..names.len()).collect();
+    builder.secondary(&secondary);
+    for (line, option) in lines {
+        let mut seen = HashSet::new();
+        let mut items = Vec::new();
+        for item in option.split_whitespace() {
+            let (name, color) = match item.split_once(':') {
+                Some((name, color)) => (name, Some(color)),
+                None => (item, None),
+            };
+            let col = builder
+                .column(name)
+                .ok_or_else(|| ParseError::UnknownColumn {
+                    line,
+                    name: name.to_string(),
+                })?;
+            if !seen.insert(col) {
+                return Err(ParseError::RepeatedColumn {
+                    line,
+                    name: name.to_string(),
+                });
+            }
+            let color = match color {
+                Some(_) if col < primary.len() => {
+                    return Err(ParseError::PrimaryColor {
+                        line,
+                        name: name.to_string(),
+                    })
+                }
+                Some(color) => builder.color(color),
+                None => 0,
+            };
+            items.push((col, color));
+        }
+        builder.colored_row(&items);
+    }
+    Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = "\
+        | the example from Knuth's Dancing Links paper\n\
+        A B C D E F G\n\
+        C E F\n\
+        A D G\n\
+        B C F\n\
+        A D\n\
+        B G\n\
+        D E G\n\
+        ";
+        let mut a = parse(text).unwrap();
+        let solns: Vec<Vec<usize>> = a.solutions().collect();
+        assert_eq!(solns, vec![vec![3, 0, 4]]);
+        assert_eq!(a.row_labels(0), vec!["C", "E", "F"]);
+    }
+
+    #[test]
+    fn test_parse_secondary() {
+        let text = "\
+        r1 r2 c1 c2 | a b\n\
+        r1 c1 a\n\
+        r1 c2 b\n\
+        r2 c1 b\n\
+        r2 c2 a\n\
+        ";
+        let mut a = parse(text).unwrap();
+        assert_eq!(a.solutions().count(), 0);
+
+        let text = "r1 r2 c1 c2 | a\nr1 c1 a\nr1 c2\nr2 c1\nr2 c2 a\n";
+        let mut a = parse(text).unwrap();
+        let solns: Vec<Vec<usize>> = a.solutions().collect();
+        assert_eq!(solns, vec![vec![1, 2]]);
+    }
+
+    #[test]
+    fn test_parse_colors() {
+        // exercise (49) of TAOCP 7.2.2.1, solved by the 2nd and 4th options
+        let text = "\
+        p q r | x y\n\
+        p q x y:A\n\
+        p r x:A y\n\
+        p x:B\n\
+        q x:A\n\
+        r y:B\n\
+        ";
+        let mut a = parse(text).unwrap();
+        let solns: Vec<Vec<usize>> = a.solutions().collect();
+        assert_eq!(solns, vec![vec![3, 1]]);
+        assert_eq!(a.row_labels(1), vec!["p", "r", "x:A", "y"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse("| nothing\n\n").err(), Some(ParseError::NoColumns));
+        assert_eq!(parse("a | b | c").err(), Some(ParseError::ExtraSeparator));
+        assert_eq!(
+            parse("a b | a").err(),
+            Some(ParseError::DuplicateColumn("a".to_string()))
+        );
+        assert_eq!(
+            parse("a b\na\nb c\n").err(),
+            Some(ParseError::UnknownColumn {
+                line: 3,
+                name: "c".to_string()
+            })
+        );
+        assert_eq!(
+            parse("a b\na a\n").err(),
+            Some(ParseError::RepeatedColumn {
+                line: 2,
+                name: "a".to_string()
+            })
+        );
+        assert_eq!(
+            parse("a | b\na:X b\n").err(),
+            Some(ParseError::PrimaryColor {
+                line: 2,
+                name: "a".to_string()
+            })
+        );
+    }
+}
@@ -1,10 +1,174 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+pub mod input;
+
 pub struct A {
     root: Node,
     headers: Vec<Node>,
+    /// The 1s of each row of the matrix, in column order.
     nodes: Vec<Vec<Node>>,
+    /// Label of each column, its index unless the builder named it.
+    labels: Vec<String>,
+    /// Names of the colours, colour `n` being at `n - 1`.
+    colors: Vec<String>,
+    /// Least and most times each column may be covered.
+    bounds: Vec<(usize, usize)>,
+    /// Times each column is covered by the rows chosen so far.
+    counts: Vec<usize>,
+    chooser: ColumnChooser,
+    stats: Stats,
+}
+
+/// How a search picks the next column to branch on.
+pub enum ColumnChooser {
+    /// The column with the fewest choices left, the first of any tied.
+    MinSize,
+    /// The first column left, in column order.
+    First,
+    /// A column picked at random among those with the fewest choices left,
+    /// from a generator seeded with the given value.
+    Random(u64),
+    /// A column picked by a closure from the columns left, given in column
+    /// order as `(column, choices)` pairs, by returning its position.
+    Custom(Box<ChooseFn>),
+}
+
+/// Closure for [`ColumnChooser::Custom`].
+pub type ChooseFn = dyn FnMut(&[(usize, usize)]) -> usize;
+
+/// Next value of a SplitMix64 generator with state `state`.
+fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Work done on an `A` by `cover`, `uncover` and the searches.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub covers: usize,
+    pub uncovers: usize,
+    /// Nodes unlinked from their column or header row while covering, and
+    /// linked back while uncovering.
+    pub updates: usize,
+    /// Search tree nodes visited at each depth.
+    pub nodes: Vec<usize>,
+}
+
+impl Stats {
+    fn visit(&mut self, depth: usize) {
+        if self.nodes.len() <= depth {
+            self.nodes.resize(depth + 1, 0);
+        }
+        self.nodes[depth] += 1;
+    }
+
+    /// Add the counts of `other` to these.
+    pub fn merge(&mut self, other: &Stats) {
+        self.covers += other.covers;
+        self.uncovers += other.uncovers;
+        self.updates += other.updates;
+        for (depth, n) in other.nodes.iter().enumerate() {
+            if self.nodes.len() <= depth {
+                self.nodes.resize(depth + 1, 0);
+            }
+            self.nodes[depth] += n;
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let per_depth: Vec<String> = self.nodes.iter().map(|n| n.to_string()).collect();
+        write!(
+            f,
+            "covers: {}, uncovers: {}, updates: {}, nodes: {} (per depth: {})",
+            self.covers,
+            self.uncovers,
+            self.updates,
+            self.nodes.iter().sum::<usize>(),
+            per_depth.join(" ")
+        )
+    }
+}
+
+impl fmt::Display for A {
+    /// Columns still to be covered, with their sizes, and the rows still
+    /// available to cover them, each as the labels of its columns.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rows = Vec::new();
+        write!(f, "columns:")?;
+        let mut header = self.get_node(self.root.right);
+        while !header.root {
+            write!(
+                f,
+                " {}({})",
+                self.label(header.addr.col as usize),
+                header.size
+            )?;
+            let mut node = self.get_node(header.down);
+            while !node.is_header {
+                rows.push(node.addr.row as usize);
+                node = self.get_node(node.down);
+            }
+            header = self.get_node(header.right);
+        }
+        writeln!(f)?;
+        rows.sort();
+        rows.dedup();
+        writeln!(f, "rows:")?;
+        for row in rows {
+            writeln!(f, "  {row}: {}", self.row_labels(row).join(" "))?;
+        }
+        Ok(())
+    }
 }
 
 impl A {
+    /// Label of column `col`.
+    pub fn label(&self, col: usize) -> &str {
+        &self.labels[col]
+    }
+
+    /// Labels of the columns row `row` covers, in column order, with
+    /// `:colour` after coloured items.
+    pub fn row_labels(&self, row: usize) -> Vec<String> {
+        self.nodes[row]
+            .iter()
+            .map(|node| {
+                let label = self.label(node.addr.col as usize);
+                match node.color.unsigned_abs() {
+                    0 => label.to_string(),
+                    c => format!("{label}:{}", self.colors[c as usize - 1]),
+                }
+            })
+            .collect()
+    }
+
+    /// Number of rows in the matrix.
+    pub fn rows(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Work done on this problem since it was built or last reset.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
+    /// Pick columns with `chooser` from now on, [`ColumnChooser::MinSize`]
+    /// being the default.
+    pub fn set_chooser(&mut self, chooser: ColumnChooser) {
+        self.chooser = chooser;
+    }
+
     fn get_node(&self, addr: Addr) -> Node {
         if addr.row < 0 {
             match addr.col {
@@ -12,10 +176,7 @@ impl A {
                 _ => self.root,
             }
         } else {
-            if (addr.row < 0) | (addr.col < 0) {
-                panic!("Invalid node addr {}{}", addr.row, addr.col);
-            }
-            self.nodes[addr.row as usize][addr.col as usize]
+            self.nodes[addr.row as usize][self.node_idx(addr)]
         }
     }
 
@@ -25,7 +186,19 @@ impl A {
         } else if node.addr.row < 0 {
             self.headers[node.addr.col as usize] = node;
         } else {
-            self.nodes[node.addr.row as usize][node.addr.col as usize] = node;
+            let idx = self.node_idx(node.addr);
+            self.nodes[node.addr.row as usize][idx] = node;
+        }
+    }
+
+    /// Position of the node at `addr` within its row.
+    fn node_idx(&self, addr: Addr) -> usize {
+        if addr.col < 0 {
+            panic!("Invalid node addr {}{}", addr.row, addr.col);
+        }
+        match self.nodes[addr.row as usize].binary_search_by_key(&addr.col, |n| n.addr.col) {
+            Ok(idx) => idx,
+            Err(_) => panic!("No node at {}{}", addr.row, addr.col),
         }
     }
 
@@ -45,19 +218,78 @@ impl A {
         self.set_node(self.get_node(node).set_down(self.get_node(other)));
     }
 
-    pub fn choose_header(&self) -> Node {
-        let mut size = usize::MAX;
+    /// The column to branch on next, picked by the [`ColumnChooser`].
+    ///
+    /// Panics if a [`ColumnChooser::Custom`] closure returns a position out of range.
+    pub fn choose_header(&mut self) -> Node {
+        match self.chooser {
+            ColumnChooser::MinSize => return self.min_header(),
+            ColumnChooser::First => return self.get_node(self.root.right),
+            _ => {}
+        }
+        let mut columns = Vec::new();
+        let mut header = self.get_node(self.root.right);
+        while !header.root {
+            columns.push((header.addr.col as usize, self.degree(header)));
+            header = self.get_node(header.right);
+        }
+        if columns.is_empty() {
+            return self.root;
+        }
+        let pos = match &mut self.chooser {
+            ColumnChooser::Random(state) => {
+                let least = columns.iter().map(|(_, d)| *d).min().unwrap();
+                let tied: Vec<usize> = (0..columns.len())
+                    .filter(|pos| columns[*pos].1 == least)
+                    .collect();
+                tied[(next_random(state) % tied.len() as u64) as usize]
+            }
+            ColumnChooser::Custom(choose) => choose(&columns),
+            _ => unreachable!(),
+        };
+        match columns.get(pos) {
+            Some((col, _)) => self.headers[*col],
+            None => panic!("Chose column {pos} of {}", columns.len()),
+        }
+    }
+
+    /// The column with the fewest ways left to cover it, see [`A::degree`].
+    fn min_header(&self) -> Node {
+        let mut degree = usize::MAX;
         let mut addr = Addr::new();
         let mut header = self.get_node(self.root.right);
         while !header.root {
-            if header.size < size {
+            if self.degree(header) < degree {
                 addr = header.addr;
-                size = header.size;
+                degree = self.degree(header);
             }
             header = self.get_node(header.right);
         }
         self.get_node(addr)
     }
+
+    /// Number of choices a search has for the column of `header`: the rows
+    /// left that can still lead to its lower bound, plus choosing no more
+    /// rows once the bound is met. For an exact cover column this is its size.
+    fn degree(&self, header: Node) -> usize {
+        let col = header.addr.col as usize;
+        let need = self.bounds[col].0.saturating_sub(self.counts[col]);
+        match need {
+            0 => header.size + 1,
+            _ if header.size < need => 0,
+            _ => header.size + 1 - need,
+        }
+    }
+
+    /// Iterate over every exact cover of this problem.
+    pub fn solutions(&mut self) -> Solutions<'_> {
+        Solutions {
+            a: self,
+            stack: Vec::new(),
+            started: false,
+            done: false,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -77,8 +309,10 @@ pub struct Node {
     root: bool,
     pub addr: Addr,
     is_header: bool,
-    is_legit: bool,
     size: usize,
+    /// Colour of a secondary item in a row, `0` for none, and negated while
+    /// a row of the same colour is committed in the column.
+    color: i32,
     header: Addr,
     left: Addr,
     right: Addr,
@@ -92,8 +326,8 @@ impl Node {
             root: false,
             addr: Addr { row, col },
             is_header,
-            is_legit: false,
             size: 0,
+            color: 0,
             header: Addr::new(),
             left: Addr::new(),
             right: Addr::new(),
@@ -165,185 +399,634 @@ impl Node {
 }
 
 /// Create a problem, `A`, from a matrix of 0s and 1s.
-pub fn from_matrix(matrix: &Vec<Vec<u8>>) -> A {
-    // the "root" col is used to start off each iteration of covering
-    let mut root = Node::new(-1, -1, true);
-    root.root = true;
-
-    let mut headers = <Vec<Node>>::new();
-    let mut nodes = <Vec<Vec<Node>>>::new();
-
-    for (r, row) in matrix.into_iter().enumerate() {
-        let mut row_nodes = <Vec<Node>>::new();
-
-        for (c, val) in row.into_iter().enumerate() {
-            if r == 0 {
-                headers.push(Node::new(-1, c as i32, true));
-                if c > 0 {
-                    headers[c] = headers[c].set_left(headers[c - 1]);
-                    headers[c - 1] = headers[c - 1].set_right(headers[c]);
-                }
-            }
-            row_nodes.push(Node::new(r as i32, c as i32, false));
-            if *val == 1 {
-                row_nodes[c].is_legit = true;
-
-                headers[c] = headers[c].incr_size();
-                row_nodes[c] = row_nodes[c].set_header(headers[c]);
-
-                if headers[c].down.row < 0 {
-                    // column's down addr will be initialised to -1, -1
-                    // so down.right -1 means no down addr set yet for a column
-                    headers[c] = headers[c].set_down(row_nodes[c]);
-                } else {
-                    // otherwise we need to find the last legit node
-                    // and connect the last legit node and the current node
-                    let mut u = r as i32;
-                    loop {
-                        u = u - 1;
-                        if u < 0 {
-                            break;
-                        }
-                        if nodes[u as usize][c].is_legit {
-                            nodes[u as usize][c] =
-                                nodes[u as usize][c].set_down(*row_nodes.last().unwrap());
-                            row_nodes[c] = row_nodes[c].set_up(nodes[u as usize][c]);
-                            break;
-                        }
-                    }
-                }
+pub fn from_matrix(matrix: &[Vec<u8>]) -> A {
+    from_matrix_with_secondary(matrix, &[])
+}
 
-                if c > 0 {
-                    let mut l = c as i32;
-                    loop {
-                        l = l - 1;
-                        if l < 0 {
-                            break;
-                        }
-                        if row_nodes[l as usize].is_legit {
-                            row_nodes[c] = row_nodes[c].set_left(row_nodes[l as usize]);
-                            row_nodes[l as usize] = row_nodes[l as usize].set_right(row_nodes[c]);
-                            break;
-                        }
-                    }
-                }
-            }
+/// Create a problem, `A`, from a matrix of 0s and 1s, where the `secondary`
+/// columns may be covered at most once rather than exactly once.
+pub fn from_matrix_with_secondary(matrix: &[Vec<u8>], secondary: &[usize]) -> A {
+    let mut builder = Builder::new(matrix.first().map_or(0, |row| row.len()));
+    builder.secondary(secondary);
+    for row in matrix {
+        let cols: Vec<usize> = (0..row.len()).filter(|c| row[*c] == 1).collect();
+        builder.row(&cols);
+    }
+    builder.build()
+}
+
+/// Builds a problem, `A`, one row at a time from the columns each row covers.
+///
+/// Only the 1s of the matrix are stored, so wide and sparse problems such as
+/// the 729 x 324 Sudoku matrix stay small.
+pub struct Builder {
+    columns: usize,
+    labels: Vec<String>,
+    names: HashMap<String, usize>,
+    secondary: Vec<bool>,
+    /// Names of the colours, colour `n` being at `n - 1`.
+    colors: Vec<String>,
+    /// Column and colour of each item of each row.
+    rows: Vec<Vec<(usize, u32)>>,
+    /// Least and most times each column may be covered.
+    bounds: Vec<(usize, usize)>,
+}
+
+/// A row named a column the [`Builder`] does not have.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownColumn(pub String);
+
+impl fmt::Display for UnknownColumn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown column {:?}", self.0)
+    }
+}
+
+impl Error for UnknownColumn {}
+
+impl Builder {
+    /// A builder for a problem with `columns` primary columns.
+    pub fn new(columns: usize) -> Builder {
+        Builder {
+            columns,
+            labels: (0..columns).map(|c| c.to_string()).collect(),
+            names: HashMap::new(),
+            secondary: vec![false; columns],
+            colors: Vec::new(),
+            rows: Vec::new(),
+            bounds: vec![(1, 1); columns],
         }
+    }
 
-        // get to the rightmost legit node
-        let mut r_ = row_nodes.len();
-        loop {
-            r_ = r_ - 1;
-            if row_nodes[r_].is_legit {
-                break;
-            }
+    /// A builder for a problem with one primary column per name, in order.
+    pub fn with_names(names: &[&str]) -> Builder {
+        let mut builder = Builder::new(names.len());
+        for (col, name) in names.iter().enumerate() {
+            builder.labels[col] = name.to_string();
+            builder.names.insert(name.to_string(), col);
         }
+        builder
+    }
 
-        // get to the leftmost legit node
-        let mut l_ = 0;
-        loop {
-            if row_nodes[l_].is_legit {
-                break;
+    /// Index of the column called `name`.
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    /// Mark `cols` as secondary, to be covered at most once rather than exactly once.
+    pub fn secondary(&mut self, cols: &[usize]) -> &mut Builder {
+        for col in cols {
+            self.secondary[*col] = true;
+        }
+        self
+    }
+
+    /// Let primary column `col` be covered at least `lo` and at most `hi`
+    /// times, rather than exactly once.
+    ///
+    /// Panics if `lo > hi` or `hi` is `0`.
+    pub fn bounds(&mut self, col: usize, lo: usize, hi: usize) -> &mut Builder {
+        if lo > hi || hi == 0 {
+            panic!("Invalid bounds {lo}..={hi} for column {col}");
+        }
+        self.bounds[col] = (lo, hi);
+        self
+    }
+
+    /// Number for the colour called `name`, to use in [`Builder::colored_row`].
+    pub fn color(&mut self, name: &str) -> u32 {
+        match self.colors.iter().position(|c| c == name) {
+            Some(idx) => idx as u32 + 1,
+            None => {
+                self.colors.push(name.to_string());
+                self.colors.len() as u32
             }
-            l_ = l_ + 1;
         }
+    }
+
+    /// Add a row covering `cols`, in any order.
+    ///
+    /// Panics if a column is out of range.
+    pub fn row(&mut self, cols: &[usize]) -> &mut Builder {
+        let items: Vec<(usize, u32)> = cols.iter().map(|c| (*c, 0)).collect();
+        self.colored_row(&items)
+    }
 
-        row_nodes[r_] = row_nodes[r_].set_right(row_nodes[l_]);
-        row_nodes[l_] = row_nodes[l_].set_left(row_nodes[r_]);
+    /// Add a row covering the columns of `items`, each with a colour from
+    /// [`Builder::color`] or `0` for none.
+    ///
+    /// Rows may share a secondary column if they give it the same colour.
+    /// Panics if a column is out of range.
+    pub fn colored_row(&mut self, items: &[(usize, u32)]) -> &mut Builder {
+        let mut items = items.to_vec();
+        items.sort();
+        items.dedup_by_key(|(c, _)| *c);
+        if let Some((col, _)) = items.iter().find(|(c, _)| *c >= self.columns) {
+            panic!("Column {col} out of range for {} columns", self.columns);
+        }
+        self.rows.push(items);
+        self
+    }
 
-        nodes.push(row_nodes);
+    /// Add a row covering the columns called `names`.
+    pub fn named_row(&mut self, names: &[&str]) -> Result<&mut Builder, UnknownColumn> {
+        let cols = names
+            .iter()
+            .map(|name| self.column(name).ok_or(UnknownColumn(name.to_string())))
+            .collect::<Result<Vec<usize>, UnknownColumn>>()?;
+        Ok(self.row(&cols))
     }
 
-    let h = nodes.len();
-    let w = headers.len();
+    /// The problem with every row added so far, rows numbered in the order they were added.
+    ///
+    /// Panics if a primary column was given a colour, or a secondary column bounds.
+    pub fn build(&self) -> A {
+        // the "root" col is used to start off each iteration of covering
+        let mut root = Node::new(-1, -1, true);
+        root.root = true;
 
-    for j in 0..w {
-        let u = headers[j].down;
-        nodes[u.row as usize][j] = nodes[u.row as usize][j].set_up(headers[j]);
-        for i in (0..h).rev() {
-            if nodes[i][j].is_legit {
-                nodes[i][j] = nodes[i][j].set_down(headers[j]);
-                headers[j] = headers[j].set_up(nodes[i][j]);
-                break;
+        let mut a = A {
+            root,
+            headers: (0..self.columns)
+                .map(|c| Node::new(-1, c as i32, true))
+                .collect(),
+            nodes: Vec::with_capacity(self.rows.len()),
+            labels: self.labels.clone(),
+            colors: self.colors.clone(),
+            bounds: self.bounds.clone(),
+            counts: vec![0; self.columns],
+            chooser: ColumnChooser::MinSize,
+            stats: Stats::default(),
+        };
+        for col in (0..self.columns).filter(|c| self.secondary[*c]) {
+            if a.bounds[col] != (1, 1) {
+                panic!("Bounds on secondary column {col}");
             }
+            a.bounds[col] = (0, 1);
+        }
+        for header in a.headers.iter_mut() {
+            *header = header.set_up(*header).set_down(*header);
         }
-    }
 
-    root = root.set_right(headers[0]);
-    root = root.set_left(*headers.last().unwrap());
+        for (r, items) in self.rows.iter().enumerate() {
+            if let Some((col, _)) = items.iter().find(|(c, k)| *k > 0 && !self.secondary[*c]) {
+                panic!("Colour on primary column {col}");
+            }
+            a.nodes.push(
+                items
+                    .iter()
+                    .map(|(c, color)| Node {
+                        color: *color as i32,
+                        ..Node::new(r as i32, *c as i32, false).set_header(a.headers[*c])
+                    })
+                    .collect(),
+            );
+            let cols: Vec<usize> = items.iter().map(|(c, _)| *c).collect();
+            for (i, c) in cols.iter().enumerate() {
+                let node = Addr {
+                    row: r as i32,
+                    col: *c as i32,
+                };
+                let header = a.headers[*c].addr;
+                let left = Addr {
+                    row: r as i32,
+                    col: cols[(i + cols.len() - 1) % cols.len()] as i32,
+                };
+                // insert at the bottom of the column, above the header
+                let above = a.headers[*c].up;
+                a.set_up(node, above);
+                a.set_down(above, node);
+                a.set_down(node, header);
+                a.set_up(header, node);
+                a.set_node(a.headers[*c].incr_size());
+                a.set_left(node, left);
+                a.set_right(left, node);
+            }
+        }
 
-    headers[0] = headers[0].set_left(root);
-    headers[w - 1] = headers[w - 1].set_right(root);
+        // primary columns are linked in a ring through the root, secondary
+        // columns only to themselves so they are never chosen to be covered
+        let mut last = a.root.addr;
+        for c in 0..self.columns {
+            let header = a.headers[c].addr;
+            if self.secondary[c] {
+                a.set_left(header, header);
+                a.set_right(header, header);
+                continue;
+            }
+            a.set_left(header, last);
+            a.set_right(last, header);
+            last = header;
+        }
+        a.set_right(last, a.root.addr);
+        a.set_left(a.root.addr, last);
 
-    return A {
-        root,
-        headers,
-        nodes,
-    };
+        a
+    }
 }
 
 pub fn cover(a: &mut A, header: Node) {
+    a.stats.covers += 1;
+    a.stats.updates += 1;
     a.set_right(header.left, header.right);
     a.set_left(header.right, header.left);
     let mut cover_node = a.get_node(header.down);
     while !cover_node.is_header {
-        let mut node = a.get_node(cover_node.right);
-        while node.addr != cover_node.addr {
-            a.set_down(node.up, node.down);
-            a.set_up(node.down, node.up);
-            a.set_node(a.get_node(node.header).decr_size());
-            node = a.get_node(node.right);
-        }
+        hide(a, cover_node);
         cover_node = a.get_node(cover_node.down);
     }
 }
 
 pub fn uncover(a: &mut A, header: Node) {
+    a.stats.uncovers += 1;
+    a.stats.updates += 1;
     let mut cover_node = a.get_node(header.up);
     while !cover_node.is_header {
-        let mut node = a.get_node(cover_node.left);
-        while node.addr != cover_node.addr {
+        unhide(a, cover_node);
+        cover_node = a.get_node(cover_node.up);
+    }
+    a.set_left(header.right, header.addr);
+    a.set_right(header.left, header.addr);
+}
+
+/// Unlink the rest of the row of `row_node` from their columns.
+///
+/// Nodes whose colour is already committed are left in place.
+fn hide(a: &mut A, row_node: Node) {
+    let mut node = a.get_node(row_node.right);
+    while node.addr != row_node.addr {
+        if node.color >= 0 {
+            a.stats.updates += 1;
+            a.set_down(node.up, node.down);
+            a.set_up(node.down, node.up);
+            a.set_node(a.get_node(node.header).decr_size());
+        }
+        node = a.get_node(node.right);
+    }
+}
+
+/// Relink what [`hide`] unlinked, in reverse order.
+fn unhide(a: &mut A, row_node: Node) {
+    let mut node = a.get_node(row_node.left);
+    while node.addr != row_node.addr {
+        if node.color >= 0 {
+            a.stats.updates += 1;
             a.set_down(node.up, node.addr);
             a.set_up(node.down, node.addr);
             a.set_node(a.get_node(node.header).incr_size());
-            node = a.get_node(node.left);
         }
-        cover_node = a.get_node(cover_node.up);
+        node = a.get_node(node.left);
+    }
+}
+
+/// Take `node`, another item of a row being chosen, out of the problem.
+///
+/// An uncoloured item counts towards its column, which is covered once it
+/// reaches its upper bound. A coloured one only hides the rows giving its
+/// column a different colour, so rows of the same colour can still share it.
+fn commit(a: &mut A, node: Node) {
+    let col = node.header.col as usize;
+    match node.color {
+        0 => {
+            a.counts[col] += 1;
+            if a.counts[col] == a.bounds[col].1 {
+                cover(a, a.get_node(node.header));
+            }
+        }
+        c if c > 0 => purify(a, node),
+        _ => {}
+    }
+}
+
+/// Undo [`commit`].
+fn uncommit(a: &mut A, node: Node) {
+    let col = node.header.col as usize;
+    match node.color {
+        0 => {
+            if a.counts[col] == a.bounds[col].1 {
+                uncover(a, a.get_node(node.header));
+            }
+            a.counts[col] -= 1;
+        }
+        c if c > 0 => unpurify(a, node),
+        _ => {}
+    }
+}
+
+/// Commit every item of `row` but the one in the column it was chosen for.
+fn choose(a: &mut A, row: Node) {
+    let mut right = a.get_node(row.right);
+    while right.addr != row.addr {
+        commit(a, right);
+        right = a.get_node(right.right);
+    }
+}
+
+/// Undo [`choose`], in reverse order.
+fn unchoose(a: &mut A, row: Node) {
+    let mut left = a.get_node(row.left);
+    while left.addr != row.addr {
+        uncommit(a, left);
+        left = a.get_node(left.left);
+    }
+}
+
+/// Unlink the row of `node` from every column, its own included.
+fn remove(a: &mut A, node: Node) {
+    hide(a, node);
+    a.set_down(node.up, node.down);
+    a.set_up(node.down, node.up);
+    a.set_node(a.get_node(node.header).decr_size());
+}
+
+/// Undo [`remove`].
+fn restore(a: &mut A, node: Node) {
+    a.set_down(node.up, node.addr);
+    a.set_up(node.down, node.addr);
+    a.set_node(a.get_node(node.header).incr_size());
+    unhide(a, node);
+}
+
+/// The choices made for one column at one depth of a search.
+///
+/// A column to be covered exactly once is covered up front and each of its
+/// rows tried in turn, as in Algorithm X. Any other column is branched on
+/// as in Knuth's Algorithm M: each row tried is removed for good before the
+/// next, so no set of rows is found twice, and once the column has been
+/// covered enough times the last choice is to cover it with no more rows.
+struct Level {
+    header: Addr,
+    exact: bool,
+    /// Row chosen, if any.
+    row: Option<Node>,
+    /// Whether the choice is to take no more rows for the column.
+    rest: bool,
+    /// Rows removed from the column so far, in order.
+    tried: Vec<Node>,
+}
+
+impl Level {
+    fn enter(a: &mut A, header: Node) -> Level {
+        let exact = a.bounds[header.addr.col as usize] == (1, 1);
+        if exact {
+            cover(a, header);
+        }
+        Level {
+            header: header.addr,
+            exact,
+            row: None,
+            rest: false,
+            tried: Vec::new(),
+        }
+    }
+
+    /// Row index of the current choice, if it is a row.
+    fn row(&self) -> Option<usize> {
+        self.row.map(|row| row.addr.row as usize)
+    }
+
+    /// Undo the current choice and make the next, returning `false` once
+    /// there are none left.
+    fn next(&mut self, a: &mut A) -> bool {
+        if self.rest {
+            return false;
+        }
+        let below = match self.row {
+            Some(row) => row.down,
+            None => a.get_node(self.header).down,
+        };
+        self.undo(a);
+        let below = a.get_node(below);
+        let col = self.header.col as usize;
+        if self.exact {
+            if below.is_header {
+                return false;
+            }
+            choose(a, below);
+            self.row = Some(below);
+            return true;
+        }
+
+        let (lo, hi) = a.bounds[col];
+        let need = lo.saturating_sub(a.counts[col]);
+        if !below.is_header && a.get_node(self.header).size >= need {
+            remove(a, below);
+            self.tried.push(below);
+            a.counts[col] += 1;
+            if a.counts[col] == hi {
+                cover(a, a.get_node(self.header));
+            }
+            choose(a, below);
+            self.row = Some(below);
+            true
+        } else if need == 0 {
+            cover(a, a.get_node(self.header));
+            self.rest = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn undo(&mut self, a: &mut A) {
+        let col = self.header.col as usize;
+        if let Some(row) = self.row.take() {
+            unchoose(a, row);
+            if !self.exact {
+                if a.counts[col] == a.bounds[col].1 {
+                    uncover(a, a.get_node(self.header));
+                }
+                a.counts[col] -= 1;
+            }
+        }
+        if self.rest {
+            uncover(a, a.get_node(self.header));
+            self.rest = false;
+        }
+    }
+
+    /// Undo every choice, leaving `a` as it was before [`Level::enter`].
+    fn leave(mut self, a: &mut A) {
+        self.undo(a);
+        for row in self.tried.iter().rev() {
+            restore(a, *row);
+        }
+        if self.exact {
+            uncover(a, a.get_node(self.header));
+        }
+    }
+}
+
+/// Hide every row with a different colour in the column of `node`, and
+/// negate the colour of the others to mark them as already agreeing with it.
+fn purify(a: &mut A, node: Node) {
+    let mut other = a.get_node(a.get_node(node.header).down);
+    while !other.is_header {
+        if other.color != node.color {
+            hide(a, other);
+        } else if other.addr != node.addr {
+            a.set_node(Node {
+                color: -other.color,
+                ..other
+            });
+        }
+        other = a.get_node(other.down);
     }
-    a.set_left(header.right, header.addr);
-    a.set_right(header.left, header.addr);
 }
 
-pub fn search(a: &mut A, depth: usize, soln: &mut Vec<usize>, soln_length: usize) {
+/// Undo [`purify`], in reverse order.
+fn unpurify(a: &mut A, node: Node) {
+    let mut other = a.get_node(a.get_node(node.header).up);
+    while !other.is_header {
+        if other.color < 0 {
+            a.set_node(Node {
+                color: -other.color,
+                ..other
+            });
+        } else if other.addr != node.addr {
+            unhide(a, other);
+        }
+        other = a.get_node(other.up);
+    }
+}
+
+/// An `A` whose rows each carry a payload, such as the move a row stands for.
+pub struct Problem<T> {
+    pub a: A,
+    payloads: Vec<T>,
+}
+
+impl<T> Problem<T> {
+    /// Attach one payload to each row of `a`, in row order.
+    ///
+    /// Panics if there is not exactly one payload per row.
+    pub fn new(a: A, payloads: Vec<T>) -> Problem<T> {
+        if payloads.len() != a.rows() {
+            panic!("{} payloads for {} rows", payloads.len(), a.rows());
+        }
+        Problem { a, payloads }
+    }
+
+    /// Payload of row `row`.
+    pub fn payload(&self, row: usize) -> &T {
+        &self.payloads[row]
+    }
+
+    /// Payloads of the rows of an exact cover, found with [`search`].
+    pub fn search(&mut self) -> Option<Vec<&T>> {
+        let mut soln = Vec::new();
+        if search(&mut self.a, 0, &mut soln) {
+            Some(soln.iter().map(|row| &self.payloads[*row]).collect())
+        } else {
+            None
+        }
+    }
+
+    /// Payloads of the rows of every exact cover, see [`A::solutions`].
+    pub fn solutions(&mut self) -> impl Iterator<Item = Vec<&T>> {
+        let payloads = &self.payloads;
+        self.a
+            .solutions()
+            .map(move |soln| soln.iter().map(|row| &payloads[*row]).collect())
+    }
+}
+
+/// Find an exact cover of `a`, returning whether there is one.
+///
+/// On success `soln` holds the row indices of the cover, otherwise it is
+/// left as it was given. `a` is left as it was given either way.
+pub fn search(a: &mut A, depth: usize, soln: &mut Vec<usize>) -> bool {
+    a.stats.visit(depth);
     if a.get_node(a.root.right).root {
-        return;
+        return true;
     }
     let header = a.choose_header();
-    cover(a, header);
-    let mut down = a.get_node(header.down);
-    while !down.is_header {
-        if (soln.len() == 0) | (depth >= soln.len()) {
-            soln.push(down.addr.row as usize);
-        } else {
-            soln[depth] = down.addr.row as usize;
+    let mut level = Level::enter(a, header);
+    let mut found = false;
+    while !found && level.next(a) {
+        soln.extend(level.row());
+        found = search(a, depth + 1, soln);
+        if !found && level.row().is_some() {
+            soln.pop();
         }
-        let mut right = a.get_node(down.right);
-        while right.addr != down.addr {
-            cover(a, a.get_node(right.header));
-            right = a.get_node(right.right);
+    }
+    level.leave(a);
+    found
+}
+
+/// Find every exact cover of `a`, passing each to `found` as row indices of the matrix.
+///
+/// The search stops as soon as `found` returns `false`, and the return value
+/// says whether it ran to completion. `a` is left as it was given either way.
+pub fn search_all(a: &mut A, found: &mut dyn FnMut(&[usize]) -> bool) -> bool {
+    a.solutions().all(|soln| found(&soln))
+}
+
+/// Number of exact covers of `a`, counting no further than `limit`.
+pub fn count_solutions(a: &mut A, limit: usize) -> usize {
+    a.solutions().take(limit).count()
+}
+
+/// Iterator over every exact cover of an `A`, each as row indices of the matrix.
+///
+/// The search runs one cover at a time with its own stack instead of
+/// recursing, so it can be stopped at any point. Dropping the iterator
+/// undoes every choice still made and leaves `A` as it was given.
+pub struct Solutions<'a> {
+    a: &'a mut A,
+    /// Column branched on and choice made at each depth.
+    stack: Vec<Level>,
+    started: bool,
+    done: bool,
+}
+
+impl Solutions<'_> {
+    /// Make the next choice at the deepest level, backing up a level when
+    /// one runs out of choices.
+    ///
+    /// Returns `false` once every level has run out.
+    fn advance(&mut self) -> bool {
+        while let Some(level) = self.stack.last_mut() {
+            if level.next(self.a) {
+                return true;
+            }
+            self.stack.pop().unwrap().leave(self.a);
+        }
+        false
+    }
+}
+
+impl Iterator for Solutions<'_> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        if self.done {
+            return None;
         }
-        search(a, depth + 1, soln, soln_length);
-        if soln.len() == soln_length {
-            return;
+        let mut resume = self.started;
+        self.started = true;
+        loop {
+            if resume && !self.advance() {
+                self.done = true;
+                return None;
+            }
+            resume = true;
+            self.a.stats.visit(self.stack.len());
+            if self.a.get_node(self.a.root.right).root {
+                return Some(self.stack.iter().filter_map(Level::row).collect());
+            }
+            let header = self.a.choose_header();
+            self.stack.push(Level::enter(self.a, header));
         }
-        let mut left = a.get_node(down.left);
-        while left.addr != down.addr {
-            uncover(a, a.get_node(left.header));
-            left = a.get_node(left.left);
+    }
+}
+
+impl Drop for Solutions<'_> {
+    fn drop(&mut self) {
+        while let Some(level) = self.stack.pop() {
+            level.leave(self.a);
         }
-        down = a.get_node(down.down);
     }
-    uncover(a, header);
 }
 
 #[cfg(test)]
@@ -373,7 +1056,403 @@ mod tests {
         ];
         let mut a = from_matrix(&matrix);
         let mut soln = Vec::new();
-        search(&mut a, 0, &mut soln, 3);
+        assert!(search(&mut a, 0, &mut soln));
         assert_eq!(soln, vec![3, 0, 4]);
+
+        // the search leaves the matrix uncovered, so it can run again
+        let mut again = Vec::new();
+        assert!(search(&mut a, 0, &mut again));
+        assert_eq!(again, soln);
+    }
+
+    #[test]
+    fn test_search_no_cover() {
+        // any two rows share a column
+        let matrix = vec![vec![1, 1, 0], vec![0, 1, 1], vec![1, 0, 1]];
+        let mut a = from_matrix(&matrix);
+        let mut soln = Vec::new();
+        assert!(!search(&mut a, 0, &mut soln));
+        assert!(soln.is_empty());
+    }
+    #[test]
+    fn test_search_all() {
+        // rows 0 and 1 each cover everything, as do rows 2 and 3 together
+        let matrix = vec![vec![1, 1, 1], vec![1, 1, 1], vec![1, 0, 0], vec![0, 1, 1]];
+        let mut a = from_matrix(&matrix);
+        let mut solns = Vec::new();
+        let done = search_all(&mut a, &mut |soln| {
+            let mut soln = soln.to_vec();
+            soln.sort();
+            solns.push(soln);
+            true
+        });
+        assert!(done);
+        assert_eq!(solns, vec![vec![0], vec![1], vec![2, 3]]);
+
+        let mut count = 0;
+        let done = search_all(&mut a, &mut |_| {
+            count += 1;
+            count < 2
+        });
+        assert!(!done);
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_count_solutions() {
+        let matrix = vec![vec![1, 1, 1], vec![1, 1, 1], vec![1, 0, 0], vec![0, 1, 1]];
+        let mut a = from_matrix(&matrix);
+        assert_eq!(count_solutions(&mut a, 10), 3);
+        assert_eq!(count_solutions(&mut a, 2), 2);
+        assert_eq!(count_solutions(&mut a, 0), 0);
+
+        let matrix = vec![vec![1, 1, 0], vec![0, 1, 1]];
+        let mut a = from_matrix(&matrix);
+        assert_eq!(count_solutions(&mut a, 10), 0);
+    }
+
+    #[test]
+    fn test_stats() {
+        let matrix = vec![
+            vec![0, 0, 1, 0, 1, 1, 0],
+            vec![1, 0, 0, 1, 0, 0, 1],
+            vec![0, 1, 1, 0, 0, 1, 0],
+            vec![1, 0, 0, 1, 0, 0, 0],
+            vec![0, 1, 0, 0, 0, 0, 1],
+            vec![0, 0, 0, 1, 1, 0, 1],
+        ];
+        let mut a = from_matrix(&matrix);
+        assert_eq!(count_solutions(&mut a, 10), 1);
+        let stats = a.stats().clone();
+        assert_eq!(stats.covers, stats.uncovers);
+        assert_eq!(stats.nodes, vec![1, 2, 2, 1]);
+        assert!(stats.updates > stats.covers);
+        // a finished search links back everything it unlinked
+        assert_eq!(stats.updates % 2, 0);
+
+        a.reset_stats();
+        assert_eq!(*a.stats(), Stats::default());
+    }
+
+    #[test]
+    fn test_solutions() {
+        let matrix = vec![vec![1, 1, 1], vec![1, 1, 1], vec![1, 0, 0], vec![0, 1, 1]];
+        let mut a = from_matrix(&matrix);
+        let solns: Vec<Vec<usize>> = a.solutions().collect();
+        assert_eq!(solns, vec![vec![0], vec![1], vec![2, 3]]);
+
+        // stopping early leaves the matrix as it was
+        let first: Vec<Vec<usize>> = a.solutions().take(1).collect();
+        assert_eq!(first, vec![vec![0]]);
+        let mut iter = a.solutions();
+        iter.next();
+        iter.next();
+        drop(iter);
+        assert_eq!(a.solutions().count(), 3);
+
+        let matrix = vec![
+            vec![0, 0, 1, 0, 1, 1, 0],
+            vec![1, 0, 0, 1, 0, 0, 1],
+            vec![0, 1, 1, 0, 0, 1, 0],
+            vec![1, 0, 0, 1, 0, 0, 0],
+            vec![0, 1, 0, 0, 0, 0, 1],
+            vec![0, 0, 0, 1, 1, 0, 1],
+        ];
+        let mut a = from_matrix(&matrix);
+        let solns: Vec<Vec<usize>> = a.solutions().collect();
+        assert_eq!(solns, vec![vec![3, 0, 4]]);
+    }
+
+    #[test]
+    fn test_secondary() {
+        // 2-queens style: rows 0 and 1 both want the last column, which is secondary
+        let matrix = vec![vec![1, 0, 1], vec![0, 1, 1], vec![1, 0, 0], vec![0, 1, 0]];
+        let mut a = from_matrix_with_secondary(&matrix, &[2]);
+        let mut solns: Vec<Vec<usize>> = a.solutions().collect();
+        solns.iter_mut().for_each(|soln| soln.sort());
+        assert_eq!(solns, vec![vec![0, 3], vec![1, 2], vec![2, 3]]);
+
+        // as a primary column it must be covered, and can't be twice
+        let mut a = from_matrix(&matrix);
+        let mut solns: Vec<Vec<usize>> = a.solutions().collect();
+        solns.iter_mut().for_each(|soln| soln.sort());
+        assert_eq!(solns, vec![vec![0, 3], vec![1, 2]]);
+
+        // a secondary column nothing covers
+        let matrix = vec![vec![1, 0], vec![1, 0]];
+        let mut a = from_matrix_with_secondary(&matrix, &[1]);
+        assert_eq!(a.solutions().count(), 2);
+    }
+
+    #[test]
+    fn test_queens() {
+        // ranks and files are primary, the 15 + 15 diagonals secondary
+        let n = 8;
+        let mut matrix = Vec::new();
+        for r in 0..n {
+            for c in 0..n {
+                let mut row = vec![0; 6 * n - 2];
+                row[r] = 1;
+                row[n + c] = 1;
+                row[2 * n + r + c] = 1;
+                row[4 * n - 1 + r + n - 1 - c] = 1;
+                matrix.push(row);
+            }
+        }
+        let secondary: Vec<usize> = (2 * n..6 * n - 2).collect();
+        let mut a = from_matrix_with_secondary(&matrix, &secondary);
+        assert_eq!(a.solutions().count(), 92);
+    }
+
+    #[test]
+    fn test_builder() {
+        let mut builder = Builder::with_names(&["a", "b", "c"]);
+        builder.named_row(&["c", "a"]).unwrap();
+        builder.row(&[1]);
+        builder.named_row(&["b", "c"]).unwrap();
+        assert_eq!(
+            builder.named_row(&["a", "d"]).err(),
+            Some(UnknownColumn("d".to_string()))
+        );
+        let mut a = builder.build();
+        let solns: Vec<Vec<usize>> = a.solutions().collect();
+        assert_eq!(solns, vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn test_builder_wide() {
+        // 40,000 columns tiled by blocks of 1,000, or by pairs of half blocks
+        let (n, block) = (40_000, 1_000);
+        let mut builder = Builder::new(n);
+        for start in (0..n).step_by(block) {
+            let cols: Vec<usize> = (start..start + block).collect();
+            builder.row(&cols);
+            builder.row(&cols[..block / 2]);
+            builder.row(&cols[block / 2..]);
+        }
+        let mut a = builder.build();
+        assert_eq!(a.solutions().take(3).count(), 3);
+        let soln = a.solutions().next().unwrap();
+        assert_eq!(soln.len(), n / block);
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut builder = Builder::with_names(&["a", "b", "c"]);
+        builder.named_row(&["a", "c"]).unwrap();
+        builder.named_row(&["b"]).unwrap();
+        builder.named_row(&["b", "c"]).unwrap();
+        let mut a = builder.build();
+        assert_eq!(a.row_labels(0), vec!["a", "c"]);
+        assert_eq!(
+            a.to_string(),
+            "columns: a(1) b(2) c(2)\nrows:\n  0: a c\n  1: b\n  2: b c\n"
+        );
+
+        let header = a.get_node(Addr { row: -1, col: 0 });
+        cover(&mut a, header);
+        assert_eq!(
+            a.to_string(),
+            "columns: b(2) c(1)\nrows:\n  1: b\n  2: b c\n"
+        );
+    }
+
+    #[test]
+    fn test_payloads() {
+        let mut builder = Builder::new(2);
+        builder.row(&[0]).row(&[1]).row(&[0, 1]);
+        let mut problem = Problem::new(builder.build(), vec!['x', 'y', 'z']);
+        assert_eq!(problem.search(), Some(vec![&'x', &'y']));
+        let solns: Vec<Vec<&char>> = problem.solutions().collect();
+        assert_eq!(solns, vec![vec![&'x', &'y'], vec![&'z']]);
+    }
+
+    #[test]
+    fn test_colors() {
+        // random small instances checked against trying every subset of rows
+        let mut seed: u64 = 7;
+        let mut next = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            ((seed >> 33) % n) as usize
+        };
+        for _ in 0..200 {
+            let (primary, secondary, rows) = (3, 2, 8);
+            let mut builder = Builder::new(primary + secondary);
+            builder.secondary(&(primary..primary + secondary).collect::<Vec<_>>());
+            let colors = [builder.color("A"), builder.color("B")];
+            let mut matrix = Vec::new();
+            for _ in 0..rows {
+                let mut row = Vec::new();
+                // every option needs a primary column for the search to pick it
+                let first = next(primary as u64);
+                for col in 0..primary + secondary {
+                    if col == first || next(2) == 0 {
+                        let color = match col < primary || next(3) == 0 {
+                            true => 0,
+                            false => colors[next(2)],
+                        };
+                        row.push((col, color));
+                    }
+                }
+                builder.colored_row(&row);
+                matrix.push(row);
+            }
+
+            let mut expected = 0;
+            for subset in 0..1u32 << rows {
+                let mut seen: Vec<Option<u32>> = vec![None; primary + secondary];
+                let mut ok = true;
+                for row in (0..rows).filter(|row| subset & 1 << row != 0) {
+                    for &(col, color) in &matrix[row] {
+                        ok &= match seen[col] {
+                            None => true,
+                            Some(prev) => col >= primary && color != 0 && prev == color,
+                        };
+                        seen[col] = Some(color);
+                    }
+                }
+                ok &= seen[..primary].iter().all(|s| s.is_some());
+                expected += ok as usize;
+            }
+            let mut a = builder.build();
+            assert_eq!(a.solutions().count(), expected);
+        }
+    }
+
+    #[test]
+    fn test_bounds() {
+        let sorted = |a: &mut A| {
+            let mut solns: Vec<Vec<usize>> = a.solutions().collect();
+            solns.iter_mut().for_each(|soln| soln.sort());
+            solns.sort();
+            solns
+        };
+
+        // one column taking one or two of three rows
+        let mut builder = Builder::new(1);
+        builder.bounds(0, 1, 2).row(&[0]).row(&[0]).row(&[0]);
+        let mut a = builder.build();
+        let before = a.to_string();
+        assert_eq!(
+            sorted(&mut a),
+            vec![
+                vec![0],
+                vec![0, 1],
+                vec![0, 2],
+                vec![1],
+                vec![1, 2],
+                vec![2]
+            ]
+        );
+        assert_eq!(a.to_string(), before);
+
+        // a covered exactly twice, b once
+        let mut builder = Builder::new(2);
+        builder.bounds(0, 2, 2);
+        builder.row(&[0, 1]).row(&[0]).row(&[0]).row(&[1]);
+        let mut a = builder.build();
+        assert_eq!(sorted(&mut a), vec![vec![0, 1], vec![0, 2], vec![1, 2, 3]]);
+        let mut soln = Vec::new();
+        assert!(search(&mut a, 0, &mut soln));
+        assert_eq!(soln, vec![0, 1]);
+        assert_eq!(
+            a.to_string(),
+            "columns: 0(3) 1(2)\nrows:\n  0: 0 1\n  1: 0\n  2: 0\n  3: 1\n"
+        );
+
+        // a primary column that may be left uncovered
+        let mut builder = Builder::new(2);
+        builder.bounds(0, 0, 1).row(&[0, 1]).row(&[1]).row(&[0]);
+        let mut a = builder.build();
+        assert_eq!(sorted(&mut a), vec![vec![0], vec![1], vec![1, 2]]);
+
+        // not enough rows to reach the lower bound
+        let mut builder = Builder::new(1);
+        builder.bounds(0, 3, 4).row(&[0]).row(&[0]);
+        let mut a = builder.build();
+        assert_eq!(a.solutions().count(), 0);
+        assert!(!search(&mut a, 0, &mut Vec::new()));
+    }
+
+    #[test]
+    fn test_bounds_random() {
+        // random small instances checked against trying every subset of rows
+        let mut seed: u64 = 11;
+        let mut next = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            ((seed >> 33) % n) as usize
+        };
+        for _ in 0..200 {
+            let (columns, rows) = (4, 9);
+            let mut builder = Builder::new(columns);
+            let mut bounds = Vec::new();
+            for col in 0..columns {
+                let lo = next(3);
+                let hi = (lo + next(3)).max(1);
+                builder.bounds(col, lo, hi);
+                bounds.push((lo, hi));
+            }
+            let mut matrix = Vec::new();
+            for _ in 0..rows {
+                let first = next(columns as u64);
+                let row: Vec<usize> = (0..columns)
+                    .filter(|col| *col == first || next(3) == 0)
+                    .collect();
+                builder.row(&row);
+                matrix.push(row);
+            }
+
+            let mut expected = 0;
+            for subset in 0..1u32 << rows {
+                let mut counts = vec![0; columns];
+                for row in (0..rows).filter(|row| subset & 1 << row != 0) {
+                    matrix[row].iter().for_each(|col| counts[*col] += 1);
+                }
+                expected += (0..columns)
+                    .all(|col| (bounds[col].0..=bounds[col].1).contains(&counts[col]))
+                    as usize;
+            }
+            let mut a = builder.build();
+            assert_eq!(a.solutions().count(), expected);
+            let mut found = 0;
+            search_all(&mut a, &mut |_| {
+                found += 1;
+                true
+            });
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_choosers() {
+        let queens = |chooser: ColumnChooser| {
+            let n = 6;
+            let mut builder = Builder::new(6 * n - 2);
+            builder.secondary(&(2 * n..6 * n - 2).collect::<Vec<_>>());
+            for r in 0..n {
+                for c in 0..n {
+                    builder.row(&[r, n + c, 2 * n + r + c, 4 * n - 1 + r + n - 1 - c]);
+                }
+            }
+            let mut a = builder.build();
+            a.set_chooser(chooser);
+            let mut solns: Vec<Vec<usize>> = a.solutions().collect();
+            solns.iter_mut().for_each(|soln| soln.sort());
+            solns.sort();
+            (solns, a.stats().nodes.iter().sum::<usize>())
+        };
+
+        let (expected, min_nodes) = queens(ColumnChooser::MinSize);
+        assert_eq!(expected.len(), 4);
+        let (solns, first_nodes) = queens(ColumnChooser::First);
+        assert_eq!(solns, expected);
+        assert!(first_nodes > min_nodes);
+        assert_eq!(queens(ColumnChooser::Random(1)).0, expected);
+        assert_eq!(
+            queens(ColumnChooser::Random(3)),
+            queens(ColumnChooser::Random(3))
+        );
+        let last = Box::new(|columns: &[(usize, usize)]| columns.len() - 1);
+        assert_eq!(queens(ColumnChooser::Custom(last)).0, expected);
     }
 }
@@ -1,17 +1,85 @@
-use dlx;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read};
+use std::process;
 
+use dlx::ColumnChooser;
+
+/// Solve an exact cover problem in Knuth's DLX format, see [`dlx::input`].
+///
+/// Usage: `dlx [--count] [--first | --seed=N] [file]`, reading stdin when no
+/// file (or `-`) is given. Prints every solution as its options, one per line,
+/// or with `--count` only the number of solutions. Columns are branched on
+/// smallest first, or in order with `--first`, or smallest first with ties
+/// broken at random with `--seed=N`.
 fn main() {
-    let matrix = vec![
-        vec![0, 0, 1, 0, 1, 1, 0],
-        vec![1, 0, 0, 1, 0, 0, 1],
-        vec![0, 1, 1, 0, 0, 1, 0],
-        vec![1, 0, 0, 1, 0, 0, 0],
-        vec![0, 1, 0, 0, 0, 0, 1],
-        vec![0, 0, 0, 1, 1, 0, 1],
-    ];
-    let mut a = dlx::from_matrix(&matrix);
-    let mut soln = Vec::new();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let paths: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    if paths.len() > 1 {
+        eprintln!("App err: more than one file given");
+        usage();
+    }
+    let path = paths.first().copied().filter(|p| *p != "-");
+
+    let mut count = false;
+    let mut chooser = ColumnChooser::MinSize;
+    for arg in args.iter().filter(|a| a.starts_with("--")) {
+        if arg == "--count" {
+            count = true;
+        } else if arg == "--first" {
+            chooser = ColumnChooser::First;
+        } else if let Some(seed) = arg.strip_prefix("--seed=") {
+            match seed.parse() {
+                Ok(seed) => chooser = ColumnChooser::Random(seed),
+                Err(e) => {
+                    eprintln!("App err: bad seed {seed:?}: {e}");
+                    process::exit(1);
+                }
+            }
+        } else {
+            eprintln!("App err: unknown option {arg}");
+            usage();
+        }
+    }
+
+    if let Err(e) = run(path, count, chooser) {
+        eprintln!("App err: {e}");
+        process::exit(1);
+    }
+}
+
+/// Print how to call the binary and exit with an error.
+fn usage() -> ! {
+    eprintln!("Usage: dlx [--count] [--first | --seed=N] [file]");
+    process::exit(1);
+}
+
+fn run(path: Option<&String>, count: bool, chooser: ColumnChooser) -> Result<(), Box<dyn Error>> {
+    let text = match path {
+        Some(path) => fs::read_to_string(path)?,
+        None => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            text
+        }
+    };
+    let mut a = dlx::input::parse(&text)?;
+    a.set_chooser(chooser);
 
-    dlx::search(&mut a, 0, &mut soln, 3);
-    println!("{:#?}", soln);
+    if count {
+        println!("{}", a.solutions().count());
+        return Ok(());
+    }
+    let options: Vec<String> = (0..a.rows()).map(|r| a.row_labels(r).join(" ")).collect();
+    let mut found = 0;
+    for soln in a.solutions() {
+        found += 1;
+        println!("Solution {found}:");
+        for row in soln {
+            println!("  {}", options[row]);
+        }
+    }
+    println!("{found} solutions");
+    Ok(())
 }
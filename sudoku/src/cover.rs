@@ -0,0 +1,213 @@
+//! Sudoku as an exact cover problem for the `dlx` solver.
+//!
+//! Each matrix row places one digit in one cell and covers the cell itself
+//! and the digit in each house holding the cell: its row, column and box,
+//! and any houses its variants add. A digit must appear exactly once in a
+//! house of one cell per digit, so those columns are primary, and at most
+//! once in a smaller one, so those are secondary.
+//!
+//! A killer cage is a house too, and also has a primary column of its own,
+//! covered by one row for each set of digits adding up to its sum. That row
+//! covers the cage's columns for every other digit, so the cells can only
+//! take digits from the set.
+//!
+//! Constraints such as thermometers have no place in the matrix, so boards
+//! with any are refused.
+
+use board::cage;
+use board::{Addr, Board};
+
+/// Columns covered by the matrix row placing `val` at `addr`: the `n * n`
+/// cells, then the digits of each house in turn.
+fn matrix_row(board: &Board, addr: &Addr, val: u8) -> Vec<usize> {
+    let n = board.size() as usize;
+    let (r, c, d) = (
+        (addr.row - 1) as usize,
+        (addr.col - 1) as usize,
+        (val - 1) as usize,
+    );
+    let mut cols = vec![n * r + c];
+    cols.extend(board.houses_of(addr).iter().map(|h| n * (n + h) + d));
+    cols
+}
+
+/// Column labels: `r3c5` for a cell, then the name of a house and a digit,
+/// such as `r3#7`, `c5#7` and `b2#7` for a row, column or box, then the
+/// name of each cage.
+fn labels(board: &Board) -> Vec<String> {
+    let n = board.size();
+    let mut labels: Vec<String> = board
+        .addrs()
+        .map(|addr| format!("r{}c{}", addr.row, addr.col))
+        .collect();
+    for house in board.houses() {
+        for d in 1..=n {
+            labels.push(format!("{}#{d}", house.name));
+        }
+    }
+    labels.extend((1..=board.cages().len()).map(|k| format!("k{k}")));
+    labels
+}
+
+/// Exact cover problem for `board`, each row carrying the placement it stands
+/// for, or `None` if it picks the digits of a cage.
+///
+/// Filled cells only get the row for their value, empty cells get one row per digit.
+///
+/// Panics if the board has constraints, see [`crate::solver::Solver::supports`].
+pub fn problem(board: &Board) -> dlx::Problem<Option<(Addr, u8)>> {
+    assert!(
+        board.constraints().is_empty(),
+        "dlx does not support constraints"
+    );
+    let n = board.size() as usize;
+    let labels = labels(board);
+    let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+    let mut builder = dlx::Builder::with_names(&labels);
+    for (h, house) in board.houses().iter().enumerate() {
+        if house.cells.len() < n {
+            builder.secondary(&(n * (n + h)..n * (n + h + 1)).collect::<Vec<_>>());
+        }
+    }
+    let mut placements = Vec::new();
+    for addr in board.addrs() {
+        let vals = match board.get(&addr) {
+            0 => (1..=board.size()).collect(),
+            val => vec![val],
+        };
+        for val in vals {
+            builder.row(&matrix_row(board, &addr, val));
+            placements.push(Some((addr, val)));
+        }
+    }
+    let houses = board.houses();
+    let all = (1 << (n + 1)) - 2;
+    for (k, cage) in board.cages().iter().enumerate() {
+        let h = board.cage_house(k);
+        for combo in cage::combinations(all, cage.cells.len() as u32, cage.sum) {
+            let mut cols = vec![n * (n + houses.len()) + k];
+            cols.extend(
+                (1..=n)
+                    .filter(|d| combo & 1 << d == 0)
+                    .map(|d| n * (n + h) + d - 1),
+            );
+            builder.row(&cols);
+            placements.push(None);
+        }
+    }
+    dlx::Problem::new(builder.build(), placements)
+}
+
+/// Solve `board` in place with Dancing Links, returning whether it was solved.
+pub fn solve(board: &mut Board) -> bool {
+    solve_with_stats(board).0
+}
+
+/// Solve `board` in place like [`solve`], with the work the search did.
+pub fn solve_with_stats(board: &mut Board) -> (bool, dlx::Stats) {
+    let mut problem = problem(board);
+    let soln: Option<Vec<(Addr, u8)>> = problem
+        .search()
+        .map(|soln| soln.into_iter().flatten().copied().collect());
+    let stats = problem.a.stats().clone();
+    match soln {
+        Some(soln) => {
+            for (addr, val) in soln {
+                board.set(&addr, val);
+            }
+            (true, stats)
+        }
+        None => (false, stats),
+    }
+}
+
+/// Every solution of `board`, found with Dancing Links.
+pub fn solve_all(board: &Board) -> Vec<Board> {
+    problem(board)
+        .solutions()
+        .map(|soln| {
+            let mut solved = board.clone();
+            for (addr, val) in soln.into_iter().flatten() {
+                solved.set(addr, *val);
+            }
+            solved
+        })
+        .collect()
+}
+
+/// Number of solutions of `board`, counting no further than `limit`.
+pub fn count_solutions(board: &Board, limit: usize) -> usize {
+    count_solutions_with_stats(board, limit).0
+}
+
+/// Count the solutions of `board` like [`count_solutions`], with the work
+/// the search did.
+pub fn count_solutions_with_stats(board: &Board, limit: usize) -> (usize, dlx::Stats) {
+    let mut problem = problem(board);
+    let count = dlx::count_solutions(&mut problem.a, limit);
+    (count, problem.a.stats().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve() {
+        let mut board = Board::new(
+            "070030000000060305001000690500200060102000407060004009013000900209050000000080040",
+        );
+        assert!(solve(&mut board));
+        assert_eq!(
+            board.line(),
+            "675938214928461375431527698594273861182695437367814529813742956249156783756389142"
+        );
+    }
+
+    #[test]
+    fn test_labels() {
+        let board = Board::new(
+            "070030000000060305001000690500200060102000407060004009013000900209050000000080040",
+        );
+        let problem = problem(&board);
+        // r1c1 is empty so its first row places a 1, r1c2 is a given 7
+        assert_eq!(
+            problem.a.row_labels(0),
+            vec!["r1c1", "r1#1", "c1#1", "b1#1"]
+        );
+        assert_eq!(problem.payload(9), &Some((Addr { row: 1, col: 2 }, 7)));
+        assert_eq!(
+            problem.a.row_labels(9),
+            vec!["r1c2", "r1#7", "c2#7", "b1#7"]
+        );
+    }
+
+    #[test]
+    fn test_sizes() {
+        let mut board = Board::new("1004 0000 0000 0320");
+        assert!(solve(&mut board));
+        assert_eq!(board.line(), "1234341221434321");
+
+        let mut board = Board::new(&"0".repeat(256));
+        assert!(solve(&mut board));
+        assert!(board.is_solved());
+        assert_eq!(count_solutions(&Board::new(&"0".repeat(16)), 1000), 288);
+    }
+
+    #[test]
+    fn test_cages() {
+        let cages = cage::parse("7 r1c1 r1c2\n5 r3c1 r4c1\n").unwrap();
+        let board = Board::new(&"0".repeat(16)).with_cages(&cages).unwrap();
+        let problem = problem(&board);
+        // 16 cells with 4 digits each, then 3 + 4 for the first cage and
+        // 1 + 4 or 2 + 3 for the second
+        assert_eq!(problem.a.rows(), 64 + 3);
+        assert_eq!(problem.payload(64), &None);
+        assert_eq!(problem.a.row_labels(64), vec!["k1#1", "k1#2", "k1"]);
+        assert_eq!(problem.a.row_labels(65), vec!["k2#2", "k2#3", "k2"]);
+        assert_eq!(problem.a.row_labels(66), vec!["k2#1", "k2#4", "k2"]);
+        // a sixth of the grids start with 3 and 4, and half of those then
+        // leave 5 for the bottom of the first column
+        assert_eq!(count_solutions(&board, 1000), 288 / 6 / 2);
+    }
+}
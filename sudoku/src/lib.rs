@@ -1,40 +1,363 @@
 use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::time::{Duration, Instant};
 
-use backtrack;
-use board;
+pub mod cover;
+pub mod solver;
+
+use board::cage::{self, Cage};
+use solver::Solver;
+
+/// What the binary has been asked to do.
+pub enum Command {
+    /// Solve the single board given on the command line.
+    Solve(String),
+    /// Solve every board, one per line, in a file or stdin when no path is given.
+    Batch(Option<String>),
+    /// Report whether every board, read like [`Command::Batch`], has a unique solution.
+    Check(Option<String>),
+}
 
 pub struct Config {
     pub method: String,
-    pub board_string: String,
+    pub command: Command,
+    /// Print the work the solver did and how long it took.
+    pub stats: bool,
+    /// File of killer cages for the board of [`Command::Solve`].
+    pub cages: Option<String>,
+    /// File of the jigsaw layout of the board of [`Command::Solve`].
+    pub layout: Option<String>,
 }
 
 impl Config {
+    /// Build a config from `<method> <board>`, `batch <method> [file]` or
+    /// `check <method> [file]`, with `--stats`, `--cages=<file>` and
+    /// `--layout=<file>` allowed anywhere, except that only a single board
+    /// takes cages or a layout.
     pub fn build(args: &[String]) -> Result<Config, &'static str> {
+        let stats = args.iter().any(|a| a == "--stats");
+        let file = |flag: &str| {
+            args.iter()
+                .find_map(|a| a.strip_prefix(flag))
+                .map(String::from)
+        };
+        let (cages, layout) = (file("--cages="), file("--layout="));
+        let args: Vec<String> = args
+            .iter()
+            .filter(|a| {
+                *a != "--stats" && !a.starts_with("--cages=") && !a.starts_with("--layout=")
+            })
+            .cloned()
+            .collect();
         if args.len() < 3 {
-            panic!("Not enough arguments");
+            return Err("Not enough arguments");
+        }
+        if args[1] == "batch" || args[1] == "check" {
+            if cages.is_some() {
+                return Err("--cages is only supported when solving a single board");
+            }
+            if layout.is_some() {
+                return Err("--layout is only supported when solving a single board");
+            }
+            let method = args[2].clone();
+            let path = args.get(3).filter(|p| *p != "-").cloned();
+            let command = match args[1].as_str() {
+                "batch" => Command::Batch(path),
+                _ => Command::Check(path),
+            };
+            return Ok(Config {
+                method,
+                command,
+                stats,
+                cages,
+                layout,
+            });
         }
         let method = args[1].clone();
         let board_string = args[2].clone();
         Ok(Config {
             method,
-            board_string,
+            command: Command::Solve(board_string),
+            stats,
+            cages,
+            layout,
         })
     }
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let mut board = board::Board::new(&config.board_string);
-    let board_string = board.string();
-    println!("{board_string}");
+/// Tally of a batch run.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub solved: usize,
+    pub unsolvable: usize,
+    pub invalid: usize,
+    pub time: Duration,
+    /// Work done over every board solved, if any were.
+    pub stats: Option<solver::Stats>,
+}
 
-    if config.method == "backtrack" {
-        backtrack::backtrack(&mut board, &board::Addr { row: 1, col: 1 }, 0);
-    } else {
-        println!("Method unknown");
+pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let solver = solver::find(&config.method).ok_or_else(|| {
+        format!(
+            "Method unknown: {}, expected one of {}",
+            config.method,
+            solver::names().join(", ")
+        )
+    })?;
+    match config.command {
+        Command::Solve(board_string) => {
+            let cages = match config.cages {
+                Some(path) => cage::parse(&fs::read_to_string(path)?)?,
+                None => Vec::new(),
+            };
+            let layout = config.layout.map(fs::read_to_string).transpose()?;
+            solve_one(
+                solver,
+                &board_string,
+                layout.as_deref(),
+                &cages,
+                config.stats,
+            )
+        }
+        Command::Batch(path) => {
+            let summary = batch(solver, open(path)?, io::stdout().lock())?;
+            eprintln!(
+                "solved: {}, unsolvable: {}, invalid: {}, time: {:.3}s",
+                summary.solved,
+                summary.unsolvable,
+                summary.invalid,
+                summary.time.as_secs_f64()
+            );
+            if let (true, Some(stats)) = (config.stats, &summary.stats) {
+                eprintln!("{stats}");
+            }
+            Ok(())
+        }
+        Command::Check(path) => {
+            let stats = check(solver, open(path)?, io::stdout().lock())?;
+            if let (true, Some(stats)) = (config.stats, &stats) {
+                eprintln!("{stats}");
+            }
+            Ok(())
+        }
     }
+}
 
+/// Reader over the file at `path`, or stdin when there is none.
+fn open(path: Option<String>) -> io::Result<Box<dyn BufRead>> {
+    Ok(match path {
+        Some(path) => Box::new(BufReader::new(File::open(path)?)),
+        None => Box::new(BufReader::new(io::stdin())),
+    })
+}
+
+fn solve_one(
+    solver: &dyn Solver,
+    board_string: &str,
+    layout: Option<&str>,
+    cages: &[Cage],
+    show_stats: bool,
+) -> Result<(), Box<dyn Error>> {
+    println!("Sudoku (Rust)");
+    let board = match layout {
+        Some(layout) => board::Board::parse_jigsaw(board_string, layout)?,
+        None => board::Board::parse(board_string)?,
+    };
+    let mut board = board.with_cages(cages)?;
+    solver.supports(&board)?;
     let board_string = board.string();
     println!("{board_string}");
 
+    let start = Instant::now();
+    let (solved, stats) = solver.solve_with_stats(&mut board);
+    let time = start.elapsed();
+    if solved {
+        if !board.is_solved() {
+            return Err(format!("{} returned an invalid solution", solver.name()).into());
+        }
+        let board_string = board.string();
+        println!("{board_string}");
+    }
+    if show_stats {
+        println!("{stats}");
+        println!("time: {:.3}s", time.as_secs_f64());
+    }
+    if !solved {
+        return Err("No solution".into());
+    }
+
     Ok(())
 }
+
+/// Solve every non-blank line of `input`, writing one line per board to `out`.
+///
+/// Solved boards are written as one line of cells, others as `no solution` or
+/// `invalid: <reason>`.
+pub fn batch(
+    solver: &dyn Solver,
+    input: impl BufRead,
+    mut out: impl Write,
+) -> Result<Summary, Box<dyn Error>> {
+    let start = Instant::now();
+    let mut summary = Summary::default();
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match board::Board::parse(&line) {
+            Ok(mut board) => {
+                let (solved, stats) = solver.solve_with_stats(&mut board);
+                match &mut summary.stats {
+                    Some(total) => total.merge(&stats),
+                    None => summary.stats = Some(stats),
+                }
+                if solved {
+                    summary.solved += 1;
+                    writeln!(out, "{}", board.line())?;
+                } else {
+                    summary.unsolvable += 1;
+                    writeln!(out, "no solution")?;
+                }
+            }
+            Err(e) => {
+                summary.invalid += 1;
+                writeln!(out, "invalid: {e}")?;
+            }
+        }
+    }
+    summary.time = start.elapsed();
+    Ok(summary)
+}
+
+/// Write `unique`, `multiple` or `invalid: <reason>` for every non-blank line of `input`.
+///
+/// A board with no solution is invalid, as is one that does not parse.
+/// Returns the work done over every board searched, if any were.
+pub fn check(
+    solver: &dyn Solver,
+    input: impl BufRead,
+    mut out: impl Write,
+) -> Result<Option<solver::Stats>, Box<dyn Error>> {
+    let mut total: Option<solver::Stats> = None;
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match board::Board::parse(&line) {
+            Ok(board) => {
+                let (count, stats) = solver.count_solutions_with_stats(&board, 2);
+                match &mut total {
+                    Some(total) => total.merge(&stats),
+                    None => total = Some(stats),
+                }
+                match count {
+                    0 => writeln!(out, "invalid: no solution")?,
+                    1 => writeln!(out, "unique")?,
+                    _ => writeln!(out, "multiple")?,
+                }
+            }
+            Err(e) => writeln!(out, "invalid: {e}")?,
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let args = |line: &str| -> Vec<String> { line.split(' ').map(String::from).collect() };
+        let config = Config::build(&args("sudoku batch mrv boards.txt --stats")).unwrap();
+        assert!(config.stats);
+        assert!(matches!(config.command, Command::Batch(Some(_))));
+        let config = Config::build(&args("sudoku check mrv boards.txt --stats")).unwrap();
+        assert!(config.stats);
+        assert!(matches!(config.command, Command::Check(Some(_))));
+        let config = Config::build(&args("sudoku --layout=jigsaw.txt mrv 0000")).unwrap();
+        assert_eq!(config.layout.as_deref(), Some("jigsaw.txt"));
+        assert!(matches!(config.command, Command::Solve(_)));
+        assert_eq!(
+            Config::build(&args("sudoku batch mrv boards.txt --layout=jigsaw.txt")).err(),
+            Some("--layout is only supported when solving a single board")
+        );
+        assert_eq!(
+            Config::build(&args("sudoku check mrv boards.txt --cages=killer.txt")).err(),
+            Some("--cages is only supported when solving a single board")
+        );
+    }
+
+    #[test]
+    fn test_batch() {
+        let input = "\
+        530070000600195000098000060800060003400803001700020006060000280000419005000080079\n\
+        \n\
+        53007\n\
+        550070000600195000098000060800060003400803001700020006060000280000419005000080079\n\
+        ";
+        let mut out = Vec::new();
+        let summary = batch(
+            &solver::Backtrack {
+                order: backtrack::Order::Naive,
+            },
+            input.as_bytes(),
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            (summary.solved, summary.unsolvable, summary.invalid),
+            (1, 0, 2)
+        );
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "534678912672195348198342567859761423426853791713924856961537284287419635345286179",
+                "invalid: expected 16, 36, 81, 144, 256 or 625 cells, found 5",
+                "invalid: given 5 at r1c1 conflicts with r1c2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let input = "\
+        530070000600195000098000060800060003400803001700020006060000280000419005000080079\n\
+        534008912672195348198342567859001423426853791713924856961537284287419635345286179\n\
+        123456780000000009000000000000000000000000000000000000000000000000000000000000000\n\
+        12345678\n\
+        ";
+        for solver in solver::SOLVERS {
+            let mut out = Vec::new();
+            let stats = check(*solver, input.as_bytes(), &mut out).unwrap();
+            assert!(stats.is_some(), "{}", solver.name());
+            let out = String::from_utf8(out).unwrap();
+            assert_eq!(
+                out.lines().collect::<Vec<&str>>(),
+                vec![
+                    "unique",
+                    "multiple",
+                    "invalid: no solution",
+                    "invalid: expected 16, 36, 81, 144, 256 or 625 cells, found 8"
+                ],
+                "{}",
+                solver.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_solve_one_unsolvable() {
+        let board_string =
+            "123456780000000009000000000000000000000000000000000000000000000000000000000000000";
+        for solver in solver::SOLVERS {
+            let got = solve_one(*solver, board_string, None, &[], false);
+            assert_eq!(got.unwrap_err().to_string(), "No solution");
+        }
+    }
+}
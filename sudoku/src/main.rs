@@ -1,10 +1,7 @@
 use std::env;
 use std::process;
 
-use sudoku;
-
 fn main() {
-    println!("Sudoku (Rust)");
     let args: Vec<String> = env::args().collect();
 
     let config = sudoku::Config::build(&args).unwrap_or_else(|err| {
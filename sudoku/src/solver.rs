@@ -0,0 +1,356 @@
+//! Common interface over the solving methods and a registry of them by name.
+
+use std::fmt;
+
+use board::Board;
+
+use crate::cover;
+
+/// A method of solving sudoku boards.
+pub trait Solver {
+    /// Name the method is selected by on the command line.
+    fn name(&self) -> &'static str;
+
+    /// Whether the method can solve `board`, or why not. The other methods
+    /// panic on a board it cannot solve.
+    fn supports(&self, _board: &Board) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    /// Solve `board` in place, returning whether a solution was found.
+    fn solve(&self, board: &mut Board) -> bool {
+        self.solve_with_stats(board).0
+    }
+
+    /// Solve `board` in place like [`Solver::solve`], with the work it took.
+    fn solve_with_stats(&self, board: &mut Board) -> (bool, Stats);
+
+    /// Every solution of `board`.
+    fn solve_all(&self, board: &Board) -> Vec<Board>;
+
+    /// Number of solutions of `board`, counting no further than `limit`.
+    fn count_solutions(&self, board: &Board, limit: usize) -> usize {
+        self.count_solutions_with_stats(board, limit).0
+    }
+
+    /// Count the solutions of `board` like [`Solver::count_solutions`], with
+    /// the work it took.
+    fn count_solutions_with_stats(&self, board: &Board, limit: usize) -> (usize, Stats);
+}
+
+/// Work done by one of the solvers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stats {
+    Backtrack(backtrack::Stats),
+    Dlx(dlx::Stats),
+}
+
+impl Stats {
+    /// Add the counts of `other` to these, if both come from the same kind of solver.
+    pub fn merge(&mut self, other: &Stats) {
+        match (self, other) {
+            (Stats::Backtrack(s), Stats::Backtrack(o)) => s.merge(o),
+            (Stats::Dlx(s), Stats::Dlx(o)) => s.merge(o),
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stats::Backtrack(s) => write!(f, "{s}"),
+            Stats::Dlx(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+/// Depth-first search over the legal values of each cell.
+pub struct Backtrack {
+    pub order: backtrack::Order,
+}
+
+impl Solver for Backtrack {
+    fn name(&self) -> &'static str {
+        match self.order {
+            backtrack::Order::Naive => "backtrack",
+            backtrack::Order::Mrv => "mrv",
+        }
+    }
+
+    fn solve_with_stats(&self, board: &mut Board) -> (bool, Stats) {
+        let mut stats = backtrack::Stats::default();
+        let solved = backtrack::solve_with_stats(board, self.order, &mut stats);
+        (solved, Stats::Backtrack(stats))
+    }
+
+    fn solve_all(&self, board: &Board) -> Vec<Board> {
+        backtrack::solve_all(board, self.order)
+    }
+
+    fn count_solutions_with_stats(&self, board: &Board, limit: usize) -> (usize, Stats) {
+        let mut stats = backtrack::Stats::default();
+        let count = backtrack::count_solutions_with_stats(board, self.order, limit, &mut stats);
+        (count, Stats::Backtrack(stats))
+    }
+}
+
+/// Knuth's Dancing Links over the exact cover encoding in [`cover`].
+pub struct Dlx;
+
+impl Solver for Dlx {
+    fn name(&self) -> &'static str {
+        "dlx"
+    }
+
+    fn supports(&self, board: &Board) -> Result<(), &'static str> {
+        match board.constraints().is_empty() {
+            true => Ok(()),
+            false => Err("dlx does not support constraints"),
+        }
+    }
+
+    fn solve_with_stats(&self, board: &mut Board) -> (bool, Stats) {
+        let (solved, stats) = cover::solve_with_stats(board);
+        (solved, Stats::Dlx(stats))
+    }
+
+    fn solve_all(&self, board: &Board) -> Vec<Board> {
+        cover::solve_all(board)
+    }
+
+    fn count_solutions_with_stats(&self, board: &Board, limit: usize) -> (usize, Stats) {
+        let (count, stats) = cover::count_solutions_with_stats(board, limit);
+        (count, Stats::Dlx(stats))
+    }
+}
+
+/// Every available solving method.
+pub static SOLVERS: &[&(dyn Solver + Sync)] = &[
+    &Backtrack {
+        order: backtrack::Order::Naive,
+    },
+    &Backtrack {
+        order: backtrack::Order::Mrv,
+    },
+    &Dlx,
+];
+
+/// The solver registered under `name`.
+pub fn find(name: &str) -> Option<&'static (dyn Solver + Sync)> {
+    SOLVERS.iter().find(|s| s.name() == name).copied()
+}
+
+/// Names of every available solving method.
+pub fn names() -> Vec<&'static str> {
+    SOLVERS.iter().map(|s| s.name()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use board::constraint::{Arrow, Constraint, Dot, Inequality, Kropki, Thermometer};
+    use board::{Addr, Variant};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_solvers_agree() {
+        let board = Board::new(
+            "534008912672195348198342567859001423426853791713924856961537284287419635345286179",
+        );
+        for solver in SOLVERS {
+            assert_eq!(solver.count_solutions(&board, 10), 2, "{}", solver.name());
+            assert_eq!(solver.count_solutions(&board, 1), 1, "{}", solver.name());
+
+            let mut solved = board.clone();
+            assert!(solver.solve(&mut solved), "{}", solver.name());
+            assert!(solved.is_solved(), "{}", solver.name());
+            assert!(
+                solver
+                    .solve_all(&board)
+                    .iter()
+                    .any(|b| b.line() == solved.line()),
+                "{}",
+                solver.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find("dlx").map(|s| s.name()), Some("dlx"));
+        assert!(find("guess").is_none());
+    }
+
+    /// Solve `blank` with `method`, then keep every other cell of the
+    /// solution as givens, read back by `parse`, and check that every solver
+    /// finds the same few solutions to them. Returns how many there are.
+    fn check_half_givens(
+        blank: Board,
+        method: &str,
+        parse: impl Fn(&str) -> Board,
+        label: &str,
+    ) -> usize {
+        let mut solved = blank;
+        assert!(find(method).unwrap().solve(&mut solved), "{label}");
+        assert!(solved.is_solved(), "{label}");
+
+        let givens: String = solved
+            .line()
+            .chars()
+            .enumerate()
+            .map(|(idx, ch)| if idx % 2 == 0 { ch } else { '0' })
+            .collect();
+        let board = parse(&givens);
+        let count = find("mrv").unwrap().count_solutions(&board, 10);
+        assert!((1..10).contains(&count), "{label}");
+        for solver in SOLVERS {
+            let name = solver.name();
+            assert_eq!(solver.count_solutions(&board, 10), count, "{label} {name}");
+            let mut got = board.clone();
+            assert!(solver.solve(&mut got), "{label} {name}");
+            assert!(got.is_solved(), "{label} {name}");
+            if count == 1 {
+                assert_eq!(got.line(), solved.line(), "{label} {name}");
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn test_jigsaw() {
+        let layout = "\
+        112222333111122333111222333444555666444556666444555566777888999777888999777888999";
+        let blank = Board::parse_jigsaw(&"0".repeat(81), layout).unwrap();
+        let parse = |givens: &str| Board::parse_jigsaw(givens, layout).unwrap();
+        // every other cell of the solution is enough to pin it down
+        assert_eq!(check_half_givens(blank, "dlx", parse, "jigsaw"), 1);
+    }
+
+    #[test]
+    fn test_variants() {
+        let blank = Board::new(&"0".repeat(81));
+        for variant in [
+            Variant::Diagonal,
+            Variant::Hyper,
+            Variant::AntiKing,
+            Variant::AntiKnight,
+        ] {
+            let parse = |givens: &str| Board::new(givens).with_variants(&[variant]).unwrap();
+            let blank = blank.with_variants(&[variant]).unwrap();
+            check_half_givens(blank, "mrv", parse, &format!("{variant:?}"));
+        }
+    }
+
+    #[test]
+    fn test_killer() {
+        // the example from Wikipedia's article on killer sudoku
+        let wikipedia = "\
+        3 r1c1 r1c2\n15 r1c3 r1c4 r1c5\n22 r1c6 r2c5 r2c6 r3c5\n4 r1c7 r2c7\n\
+        16 r1c8 r2c8\n15 r1c9 r2c9 r3c9 r4c9\n25 r2c1 r2c2 r3c1 r3c2\n17 r2c3 r2c4\n\
+        9 r3c3 r3c4 r4c4\n8 r3c6 r4c6 r5c6\n20 r3c7 r3c8 r4c7\n6 r4c1 r5c1\n\
+        14 r4c2 r4c3\n17 r4c5 r5c5 r6c5\n17 r4c8 r5c7 r5c8\n13 r5c2 r5c3 r6c2\n\
+        20 r5c4 r6c4 r7c4\n12 r5c9 r6c9\n27 r6c1 r7c1 r8c1 r9c1\n6 r6c3 r7c2 r7c3\n\
+        20 r6c6 r7c6 r7c7\n6 r6c7 r6c8\n10 r7c5 r8c4 r8c5 r9c4\n\
+        14 r7c8 r7c9 r8c8 r8c9\n8 r8c2 r9c2\n16 r8c3 r9c3\n15 r8c6 r8c7\n\
+        13 r9c5 r9c6 r9c7\n17 r9c8 r9c9\n";
+        let small = "\
+        6 r1c1 r2c1 r3c1\n7 r1c2 r2c2 r2c3\n7 r1c3 r1c4\n5 r2c4 r3c4\n\
+        8 r3c2 r4c2 r4c1\n7 r3c3 r4c3 r4c4\n";
+        for (cages, givens, solution) in [
+            (
+                wikipedia,
+                "0".repeat(81),
+                "215647398368952174794381652586274931142593867973816425821739546659428713437165289",
+            ),
+            (small, "0".repeat(16), "1234341221434321"),
+            // a given in place of the cage holding r1c1
+            (
+                small.split_once('\n').unwrap().1,
+                "1000000000000000".to_string(),
+                "1234341221434321",
+            ),
+        ] {
+            let cages = board::cage::parse(cages).unwrap();
+            let board = Board::new(&givens).with_cages(&cages).unwrap();
+            for solver in SOLVERS {
+                let name = solver.name();
+                assert_eq!(solver.count_solutions(&board, 2), 1, "{name}");
+                let mut got = board.clone();
+                assert!(solver.solve(&mut got), "{name}");
+                assert_eq!(got.line(), solution, "{name}");
+                assert!(got.is_solved(), "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_cage() {
+        // twelve cells of a 16x16 adding up to 78 must hold 1 to 12
+        let cells: Vec<Addr> = (1..=12).map(|col| Addr { row: 1, col }).collect();
+        let cage = board::cage::Cage { sum: 78, cells };
+        let board = Board::new(&"0".repeat(256))
+            .with_cages(std::slice::from_ref(&cage))
+            .unwrap();
+        for solver in SOLVERS {
+            let name = solver.name();
+            let mut got = board.clone();
+            assert!(solver.solve(&mut got), "{name}");
+            assert!(got.is_solved(), "{name}");
+            let mut digits: Vec<u8> = cage.cells.iter().map(|a| got.get(a)).collect();
+            digits.sort();
+            assert_eq!(digits, (1..=12).collect::<Vec<_>>(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_constraints() {
+        let addr = |row, col| Addr { row, col };
+        let constraints: Vec<Arc<dyn Constraint>> = vec![
+            Arc::new(Inequality {
+                less: addr(1, 2),
+                greater: addr(1, 1),
+            }),
+            Arc::new(Thermometer {
+                cells: vec![addr(3, 6), addr(3, 7), addr(3, 8), addr(3, 9)],
+            }),
+            Arc::new(Arrow {
+                circle: addr(5, 8),
+                cells: vec![addr(4, 9), addr(4, 8), addr(4, 7)],
+            }),
+            Arc::new(Kropki {
+                a: addr(1, 4),
+                b: addr(1, 5),
+                dot: Dot::White,
+            }),
+            Arc::new(Kropki {
+                a: addr(2, 8),
+                b: addr(2, 9),
+                dot: Dot::Black,
+            }),
+        ];
+        // the givens on the constraints are taken out of a puzzle with a
+        // unique solution
+        let givens =
+            "000000000600195000098000000800060000400803001700020006060000280000419005000080079";
+        let board = Board::new(givens);
+        assert!(find("mrv").unwrap().count_solutions(&board, 2) > 1);
+        let board = board.with_constraints(&constraints).unwrap();
+        assert_eq!(
+            find("dlx").unwrap().supports(&board),
+            Err("dlx does not support constraints")
+        );
+        for solver in SOLVERS.iter().filter(|s| s.supports(&board).is_ok()) {
+            let name = solver.name();
+            assert_eq!(solver.count_solutions(&board, 2), 1, "{name}");
+            let mut got = board.clone();
+            assert!(solver.solve(&mut got), "{name}");
+            assert_eq!(
+                got.line(),
+                "534678912672195348198342567859761423426853791713924856961537284287419635345286179",
+                "{name}"
+            );
+            assert!(got.is_solved(), "{name}");
+        }
+    }
+}